@@ -0,0 +1,92 @@
+//! Clipboard access.
+//!
+//! The `clipboard` config key picks the mechanism: `system` (the native
+//! clipboard via arboard, the default), `osc52` (an escape sequence the terminal
+//! turns into a copy, which also works over SSH), or any other value as a
+//! command that receives the text on stdin, e.g. `wl-copy` or `xclip -sel clip`.
+
+use crate::config;
+use crate::editor::parse_cmdline;
+use crate::fsutil::find_program;
+use crate::os::OsKind;
+use arboard::Clipboard;
+use base64::Engine;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Put `text` on the clipboard.
+pub fn copy(text: &str) -> io::Result<()> {
+    match config::get().clipboard.as_str() {
+        "" | "system" => {
+            let mut clipboard = Clipboard::new().map_err(io::Error::other)?;
+            clipboard.set_text(text).map_err(io::Error::other)
+        }
+        "osc52" => copy_osc52(text),
+        command => copy_with_command(command, text),
+    }
+}
+
+/// Check that the clipboard can be used (e.g. a display server is reachable),
+/// without changing its contents.
+pub fn check() -> io::Result<()> {
+    match config::get().clipboard.as_str() {
+        "" | "system" => Clipboard::new().map(drop).map_err(io::Error::other),
+        "osc52" => OpenOptions::new()
+            .write(true)
+            .open(OsKind::current().tty_path())
+            .map(drop),
+        // Only look the program up: running it, even with empty input, could
+        // clear the clipboard.
+        command => {
+            let program = parse_cmdline(command)
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            match find_program(&program) {
+                Some(_) => Ok(()),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("clipboard command '{}' was not found", program),
+                )),
+            }
+        }
+    }
+}
+
+fn copy_osc52(text: &str) -> io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut tty = OpenOptions::new()
+        .write(true)
+        .open(OsKind::current().tty_path())?;
+    write!(tty, "\x1b]52;c;{}\x07", encoded)?;
+    tty.flush()
+}
+
+fn copy_with_command(command: &str, text: &str) -> io::Result<()> {
+    let mut parts = parse_cmdline(command);
+    if parts.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "empty clipboard command",
+        ));
+    }
+    let program = parts.remove(0);
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "clipboard command exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,440 @@
+//! User configuration read from `config.toml` in the config root.
+//!
+//! The file is loaded once at startup by `main` (see [`init`]) and read
+//! everywhere else through [`get`]. Every key is optional; anything missing
+//! takes the default shown by `snipman config list`. Keys are addressed with
+//! dotted paths such as `tui.keys.quit` or `search.weights.code`.
+
+use crate::fsutil::write_atomic;
+use crate::init::user_dirs;
+use crate::snippets::SortKey;
+use crate::store::Backend;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::{fs, io};
+use toml::{Table, Value};
+
+/// Everything that can be set in `config.toml`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory for snippets and other data, instead of the per-OS default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// How snippets are stored: `json` files or a `sqlite` database.
+    /// `SNIPMAN_BACKEND` overrides it.
+    pub backend: Backend,
+    /// Read-only snippet directories (e.g. a network share or a git checkout)
+    /// listed under the user's own snippets.
+    pub shared_libraries: Vec<PathBuf>,
+    /// Editor command line, preferred over $VISUAL and $EDITOR.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// How to copy: `system` (native clipboard), `osc52` (terminal escape
+    /// sequence, works over SSH), or a command that reads the text on stdin
+    /// such as `wl-copy`.
+    pub clipboard: String,
+    /// Sort order of `list` and the TUI when no query is typed.
+    pub default_sort: SortKey,
+    /// Refresh man page and completions automatically after an upgrade.
+    pub auto_upgrade: bool,
+    pub tui: TuiConfig,
+    pub search: SearchConfig,
+    pub git: GitConfig,
+    pub trash: TrashConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: None,
+            backend: Backend::default(),
+            shared_libraries: Vec::new(),
+            editor: None,
+            clipboard: "system".to_string(),
+            default_sort: SortKey::default(),
+            auto_upgrade: true,
+            tui: TuiConfig::default(),
+            search: SearchConfig::default(),
+            git: GitConfig::default(),
+            trash: TrashConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuiConfig {
+    /// Body lines shown by the compact preview.
+    pub preview_lines: usize,
+    pub theme: Theme,
+    pub keys: Keys,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            preview_lines: 10,
+            theme: Theme::default(),
+            keys: Keys::default(),
+        }
+    }
+}
+
+/// TUI colors: names (`yellow`), indexes (`42`) or hex (`#009696`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub highlight_fg: String,
+    pub highlight_bg: String,
+    pub border: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            highlight_fg: "reset".to_string(),
+            highlight_bg: "#009696".to_string(),
+            border: "reset".to_string(),
+        }
+    }
+}
+
+/// Single-character TUI hotkeys, all pressed with Ctrl so they never take a
+/// letter away from the search box.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keys {
+    pub quit: char,
+    /// Copy the snippet as written, without filling in its placeholders.
+    pub copy_raw: char,
+    pub run: char,
+    pub edit: char,
+    pub details: char,
+    pub add: char,
+    pub delete: char,
+    pub preview: char,
+    /// Copy a shared snippet into your library so it can be changed.
+    pub fork: char,
+    /// Open the library switcher.
+    pub library: char,
+    /// Bring back the snippet deleted last in this session.
+    pub undo: char,
+}
+
+impl Keys {
+    /// Every binding with its key name.
+    fn bindings(&self) -> [(&'static str, char); 11] {
+        [
+            ("quit", self.quit),
+            ("copy_raw", self.copy_raw),
+            ("run", self.run),
+            ("edit", self.edit),
+            ("details", self.details),
+            ("add", self.add),
+            ("delete", self.delete),
+            ("preview", self.preview),
+            ("fork", self.fork),
+            ("library", self.library),
+            ("undo", self.undo),
+        ]
+    }
+
+    /// Reject letters whose Ctrl chord most terminals send as another key
+    /// (Ctrl-H Backspace, Ctrl-I Tab, Ctrl-J and Ctrl-M Enter), so the
+    /// binding could never fire.
+    fn check(&self) -> io::Result<()> {
+        for (name, key) in self.bindings() {
+            if let Some(sent) = match key.to_ascii_lowercase() {
+                'h' => Some("Backspace"),
+                'i' => Some("Tab"),
+                'j' | 'm' => Some("Enter"),
+                _ => None,
+            } {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "tui.keys.{} = '{}' cannot be used: terminals send Ctrl-{} as {}",
+                        name,
+                        key,
+                        key.to_ascii_uppercase(),
+                        sent
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self {
+            quit: 'q',
+            copy_raw: 'y',
+            run: 'x',
+            edit: 'e',
+            details: 'r',
+            add: 'a',
+            delete: 'd',
+            preview: 'p',
+            fork: 'f',
+            library: 'l',
+            undo: 'u',
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub weights: Weights,
+}
+
+/// Multipliers applied to the fuzzy score of each field before taking the best.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    pub description: i64,
+    pub tags: i64,
+    pub code: i64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            description: 1,
+            tags: 1,
+            code: 1,
+        }
+    }
+}
+
+/// Version the snippet directory with git, see `snipman sync`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitConfig {
+    /// Commit every change to the snippet directory.
+    pub enabled: bool,
+    /// URL `sync` pulls from and pushes to when the repository has no `origin` yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    pub branch: String,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            remote: None,
+            branch: "main".to_string(),
+        }
+    }
+}
+
+/// Deleted snippets kept in the trash, see `snipman trash`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    /// Days a deleted snippet stays restorable before it is purged; 0 keeps it forever.
+    pub retention_days: u64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Location of `config.toml`.
+pub fn path() -> io::Result<PathBuf> {
+    Ok(user_dirs()?.config_root.join("config.toml"))
+}
+
+/// Read the config file. A missing file yields the defaults.
+///
+/// # Errors
+/// `InvalidData` if the file is not valid TOML or has unknown keys or wrong types.
+pub fn load() -> io::Result<Config> {
+    parse(&read_table()?)
+}
+
+/// Install the configuration returned by [`get`]. Only the first call has an effect.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// The configuration loaded at startup, or the defaults if [`init`] was not called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+fn read_table() -> io::Result<Table> {
+    match fs::read_to_string(path()?) {
+        Ok(text) => text
+            .parse::<Table>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Table::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse(table: &Table) -> io::Result<Config> {
+    let config: Config = Value::Table(table.clone())
+        .try_into()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    config.tui.keys.check()?;
+    Ok(config)
+}
+
+/// `config` as `(key, value)` pairs with dotted keys.
+fn entries(config: &Config) -> io::Result<Vec<(String, Value)>> {
+    let mut entries = Vec::new();
+    if let Value::Table(table) = Value::try_from(config).map_err(io::Error::other)? {
+        flatten("", &table, &mut entries);
+    }
+    Ok(entries)
+}
+
+/// Effective settings (file values over defaults), flattened to dotted keys.
+pub fn list() -> io::Result<Vec<(String, Value)>> {
+    entries(&load()?)
+}
+
+fn is_known(key: &str) -> io::Result<bool> {
+    Ok(OPTIONAL_KEYS.contains(&key) || entries(&Config::default())?.iter().any(|(k, _)| k == key))
+}
+
+fn flatten(prefix: &str, table: &Table, out: &mut Vec<(String, Value)>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Table(inner) => flatten(&key, inner, out),
+            other => out.push((key, other.clone())),
+        }
+    }
+}
+
+/// Effective value of a dotted `key`, or `None` if it is a known key that is unset.
+///
+/// # Errors
+/// `InvalidInput` for unknown keys.
+pub fn get_key(key: &str) -> io::Result<Option<Value>> {
+    if !is_known(key)? {
+        return Err(unknown_key(key));
+    }
+    Ok(list()?.into_iter().find(|(k, _)| k == key).map(|(_, v)| v))
+}
+
+/// Keys without a default, so they are missing from [`list`] until set.
+const OPTIONAL_KEYS: [&str; 3] = ["data_dir", "editor", "git.remote"];
+
+/// Set a dotted `key` in the config file and return the stored value.
+///
+/// `value` is read as a TOML literal (`3`, `true`) when that fits the key's
+/// type, otherwise as a plain string. The file is only written if the result
+/// is a valid configuration, and is replaced atomically.
+pub fn set_key(key: &str, value: &str) -> io::Result<Value> {
+    if !is_known(key)? {
+        return Err(unknown_key(key));
+    }
+    let mut candidates = Vec::new();
+    if let Ok(literal) = format!("v = {}", value).parse::<Table>()
+        && let Some(literal) = literal.get("v")
+    {
+        candidates.push(literal.clone());
+    }
+    candidates.push(Value::String(value.to_string()));
+
+    let mut table = read_table()?;
+    let mut last_error = None;
+    for candidate in candidates {
+        let mut updated = table.clone();
+        insert(&mut updated, key, candidate.clone());
+        match parse(&updated) {
+            Ok(_) => {
+                table = updated;
+                let path = path()?;
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                write_atomic(
+                    &path,
+                    toml::to_string_pretty(&table)
+                        .map_err(io::Error::other)?
+                        .as_bytes(),
+                )?;
+                return Ok(candidate);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| unknown_key(key)))
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = table
+                .entry(head)
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            if let Value::Table(inner) = entry {
+                insert(inner, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+fn unknown_key(key: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("unknown config key '{}' (see `snipman config list`)", key),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_files_fill_in_defaults() {
+        let table: Table =
+            "default_sort = \"updated\"\nbackend = \"sqlite\"\n[tui.keys]\nquit = \"Q\"\n"
+                .parse()
+                .unwrap();
+        let config = parse(&table).unwrap();
+        assert_eq!(config.default_sort, SortKey::Updated);
+        assert_eq!(config.backend, Backend::Sqlite);
+        assert_eq!(Config::default().backend, Backend::Json);
+        assert_eq!(config.tui.keys.quit, 'Q');
+        assert_eq!(config.tui.keys.run, 'x');
+        assert_eq!(config.tui.preview_lines, 10);
+
+        let unknown: Table = "colour = \"red\"".parse().unwrap();
+        assert!(parse(&unknown).is_err());
+        let backend: Table = "backend = \"postgres\"".parse().unwrap();
+        assert!(parse(&backend).is_err());
+        let backspace: Table = "[tui.keys]\nedit = \"H\"".parse().unwrap();
+        assert!(parse(&backspace).is_err());
+    }
+
+    #[test]
+    fn insert_creates_nested_tables() {
+        let mut table = Table::new();
+        insert(&mut table, "search.weights.code", Value::Integer(3));
+        assert_eq!(parse(&table).unwrap().search.weights.code, 3);
+    }
+}
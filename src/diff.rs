@@ -0,0 +1,80 @@
+//! Line diffs between snippet versions, for `snipman diff`.
+
+/// One line of a diff.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl Line<'_> {
+    /// The line with a `-`, `+` or space prefix, as in a unified diff.
+    pub fn render(&self) -> String {
+        match self {
+            Line::Same(text) => format!("  {}", text),
+            Line::Removed(text) => format!("- {}", text),
+            Line::Added(text) => format!("+ {}", text),
+        }
+    }
+}
+
+/// Lines of `old` and `new` in order, marked as kept, removed or added.
+///
+/// Uses a longest-common-subsequence table, which is fine for snippet-sized
+/// inputs (quadratic in the number of lines).
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // common[i][j]: length of the LCS of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            out.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            out.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            out.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    out.extend(old[i..].iter().map(|l| Line::Removed(l)));
+    out.extend(new[j..].iter().map(|l| Line::Added(l)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_changed_lines() {
+        let diff = lines("a\nb\nc\n", "a\nB\nc\nd");
+        assert_eq!(
+            diff,
+            [
+                Line::Same("a"),
+                Line::Removed("b"),
+                Line::Added("B"),
+                Line::Same("c"),
+                Line::Added("d"),
+            ]
+        );
+        assert_eq!(diff[1].render(), "- b");
+    }
+}
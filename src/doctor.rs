@@ -0,0 +1,493 @@
+//! `snipman doctor`: diagnose installation and environment problems.
+//!
+//! Each check reports pass, warn or fail with a suggested fix. The only write
+//! is an empty `.doctor<pid>` probe file created and removed again in the data
+//! directory to test that it is writable (delete it if doctor was killed in
+//! between). Nothing else is modified, so it is safe to run at any time,
+//! including before `install`.
+
+use crate::clipboard;
+use crate::config;
+use crate::editor::editor_command;
+use crate::fsutil::find_program;
+use crate::init::{self, state, UserDirs};
+use crate::shell::ShellTarget;
+use crate::snippets::{get_data_root, get_snippets_dir};
+use crate::store::{self, Backend, JsonDirStore};
+use clap_complete::Shell;
+use std::path::Path;
+use std::{env, fmt, fs};
+
+/// Outcome of a single check.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    /// Works, but something is likely to bite later.
+    Warn,
+    Fail,
+}
+
+/// Result of one diagnostic.
+pub struct Check {
+    pub status: Status,
+    pub name: String,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            status: Status::Pass,
+            name: name.into(),
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        status: Status,
+        name: impl Into<String>,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            status,
+            name: name.into(),
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.status {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        write!(f, "[{}] {}: {}", label, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n       fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check, in report order.
+pub fn run() -> Vec<Check> {
+    let dirs = match init::user_dirs() {
+        Ok(dirs) => dirs,
+        Err(e) => {
+            return vec![Check::problem(
+                Status::Fail,
+                "home directory",
+                e.to_string(),
+                "set $HOME",
+            )];
+        }
+    };
+    let installed = state::read_install_state().ok().flatten();
+    let target = installed
+        .as_ref()
+        .and_then(|s| s.options.as_ref())
+        .map_or(ShellTarget::Auto, |o| o.shell.clone());
+
+    let mut checks = vec![check_install_stamp(), check_man_page(&dirs)];
+    checks.extend(check_completions(&dirs, &target));
+    checks.push(check_manpath(&dirs));
+    checks.extend(check_completion_loading(&dirs));
+    checks.push(check_config());
+    checks.push(check_clipboard());
+    checks.push(check_editor());
+    checks.push(check_data_dir());
+    checks.push(check_snippet_files());
+    checks.extend(check_shared_libraries());
+    checks.extend(check_git());
+    checks
+}
+
+fn check_install_stamp() -> Check {
+    const NAME: &str = "install stamp";
+    let state = match state::read_install_state() {
+        Ok(Some(state)) => state,
+        Ok(None) => {
+            return Check::problem(Status::Fail, NAME, "not installed", "run `snipman install`");
+        }
+        Err(e) => {
+            return Check::problem(
+                Status::Fail,
+                NAME,
+                format!("unreadable: {}", e),
+                "run `snipman install` to rewrite it",
+            );
+        }
+    };
+    if state.version != env!("CARGO_PKG_VERSION") {
+        return Check::problem(
+            Status::Warn,
+            NAME,
+            format!(
+                "written by snipman {} (this is {})",
+                state.version,
+                env!("CARGO_PKG_VERSION")
+            ),
+            "run `snipman install` to refresh the man page and completions",
+        );
+    }
+    match init::check_install() {
+        Ok(drift) if drift.is_empty() => Check::pass(
+            NAME,
+            format!("version {}, all installed files match", state.version),
+        ),
+        Ok(drift) => Check::problem(
+            Status::Warn,
+            NAME,
+            drift
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "),
+            "run `snipman install` (add --force to overwrite your edits)",
+        ),
+        Err(e) => Check::problem(Status::Warn, NAME, e.to_string(), "run `snipman install`"),
+    }
+}
+
+fn check_man_page(dirs: &UserDirs) -> Check {
+    let path = dirs.man1.join("snipman.1");
+    if path.is_file() {
+        Check::pass("man page", path.display().to_string())
+    } else {
+        Check::problem(
+            Status::Fail,
+            "man page",
+            format!("{} is missing", path.display()),
+            "run `snipman install`",
+        )
+    }
+}
+
+fn check_completions(dirs: &UserDirs, target: &ShellTarget) -> Vec<Check> {
+    target
+        .to_shells()
+        .into_iter()
+        .filter_map(|sh| {
+            let path = init::completion_path(dirs, sh, "snipman")?;
+            let name = format!("{:?} completion", sh);
+            Some(if path.is_file() {
+                Check::pass(name, path.display().to_string())
+            } else {
+                Check::problem(
+                    Status::Fail,
+                    name,
+                    format!("{} is missing", path.display()),
+                    format!("run `snipman install {:?}`", sh).to_lowercase(),
+                )
+            })
+        })
+        .collect()
+}
+
+fn check_manpath(dirs: &UserDirs) -> Check {
+    let man_dir = dirs.home.join(".local/share/man");
+    let Some(manpath) = env::var_os("MANPATH") else {
+        return Check::problem(
+            Status::Warn,
+            "MANPATH",
+            "not set; `man` relies on its defaults to find ~/.local/share/man",
+            "open a new shell after `snipman install`, or add ~/.local/share/man to MANPATH",
+        );
+    };
+    if env::split_paths(&manpath).any(|p| p == man_dir) {
+        Check::pass("MANPATH", format!("includes {}", man_dir.display()))
+    } else {
+        Check::problem(
+            Status::Warn,
+            "MANPATH",
+            format!("does not include {}", man_dir.display()),
+            format!("export MANPATH=\"{}:$MANPATH\"", man_dir.display()),
+        )
+    }
+}
+
+/// Whether the detected shell will actually load the installed completions.
+fn check_completion_loading(dirs: &UserDirs) -> Option<Check> {
+    match ShellTarget::detect()? {
+        ShellTarget::Zsh => {
+            let zshrc = init::rc_file(dirs, Shell::Zsh)?;
+            let zsh_dir = dirs.zsh.to_string_lossy().into_owned();
+            let on_fpath = fs::read_to_string(&zshrc).is_ok_and(|rc| rc.contains(&zsh_dir))
+                || env::var("FPATH").is_ok_and(|fpath| fpath.split(':').any(|p| p == zsh_dir));
+            Some(if on_fpath {
+                Check::pass(
+                    "zsh fpath",
+                    format!("{} includes {}", zshrc.display(), zsh_dir),
+                )
+            } else {
+                Check::problem(
+                    Status::Fail,
+                    "zsh fpath",
+                    format!("{} is not on fpath", zsh_dir),
+                    format!(
+                        "run `snipman install` without --no-modify-rc, or add `fpath+=({})` before compinit in {}",
+                        zsh_dir,
+                        zshrc.display()
+                    ),
+                )
+            })
+        }
+        ShellTarget::Bash => {
+            let candidates = [
+                "/usr/share/bash-completion/bash_completion",
+                "/etc/bash_completion",
+                "/opt/homebrew/etc/profile.d/bash_completion.sh",
+                "/usr/local/etc/profile.d/bash_completion.sh",
+            ];
+            Some(match candidates.iter().find(|p| Path::new(p).is_file()) {
+                Some(found) => Check::pass("bash-completion", *found),
+                None => Check::problem(
+                    Status::Warn,
+                    "bash-completion",
+                    "not found; bash only loads completions from ~/.local/share via bash-completion",
+                    "install your system's bash-completion package",
+                ),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn check_config() -> Check {
+    let path = match config::path() {
+        Ok(path) => path,
+        Err(e) => return Check::problem(Status::Fail, "config file", e.to_string(), "set $HOME"),
+    };
+    if !path.exists() {
+        return Check::pass(
+            "config file",
+            format!("{} not present, using defaults", path.display()),
+        );
+    }
+    match config::load() {
+        Ok(_) => Check::pass("config file", path.display().to_string()),
+        Err(e) => Check::problem(
+            Status::Fail,
+            "config file",
+            e.to_string().replace('\n', " "),
+            format!(
+                "fix {} (see `snipman config list` for valid keys)",
+                path.display()
+            ),
+        ),
+    }
+}
+
+fn check_clipboard() -> Check {
+    let fix = match config::get().clipboard.as_str() {
+        "" | "system" | "osc52" => {
+            "run inside a graphical session, or use `snipman get` / `snipman interactive --print` instead"
+        }
+        _ => "install the command, or switch back with `snipman config set clipboard system`",
+    };
+    match clipboard::check() {
+        Ok(()) => Check::pass("clipboard", "available"),
+        Err(e) => Check::problem(Status::Fail, "clipboard", e.to_string(), fix),
+    }
+}
+
+fn check_editor() -> Check {
+    // Same order as `editor_command`.
+    let (source, fix) = if config::get().editor.is_some() {
+        (
+            "config",
+            "set `editor` to an installed editor, e.g. `snipman config set editor vi`",
+        )
+    } else if env::var_os("VISUAL").is_some() {
+        (
+            "$VISUAL",
+            "set $VISUAL to an installed editor, e.g. `export VISUAL=vi`",
+        )
+    } else if env::var_os("EDITOR").is_some() {
+        (
+            "$EDITOR",
+            "set $EDITOR to an installed editor, e.g. `export EDITOR=vi`",
+        )
+    } else {
+        (
+            "default",
+            "set $EDITOR to an installed editor, e.g. `export EDITOR=vi`",
+        )
+    };
+    match editor_command() {
+        Ok((program, _)) => match find_program(&program) {
+            Some(path) => Check::pass("editor", format!("{} ({})", path.display(), source)),
+            None => Check::problem(
+                Status::Fail,
+                "editor",
+                format!("'{}' ({}) was not found", program, source),
+                fix,
+            ),
+        },
+        Err(e) => Check::problem(Status::Fail, "editor", e.to_string(), fix),
+    }
+}
+
+fn check_data_dir() -> Check {
+    let root = get_data_root();
+    if !root.exists() {
+        return Check::problem(
+            Status::Warn,
+            "data directory",
+            format!("{} does not exist yet", root.display()),
+            "run `snipman install` or add a snippet to create it",
+        );
+    }
+    // Permission bits do not account for ACLs, read-only mounts or the
+    // current user, so actually try.
+    let probe = root.join(format!(".doctor{}", std::process::id()));
+    let result = fs::write(&probe, b"").and_then(|()| fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::pass("data directory", format!("{} is writable", root.display())),
+        Err(e) => Check::problem(
+            Status::Fail,
+            "data directory",
+            format!("cannot write to {}: {}", root.display(), e),
+            format!("check ownership and permissions of {}", root.display()),
+        ),
+    }
+}
+
+/// Checks the storage of the configured backend.
+fn check_snippet_files() -> Check {
+    match store::backend() {
+        Ok(Backend::Json) => check_json_files(),
+        Ok(Backend::Sqlite) => check_database(),
+        Err(e) => Check::problem(
+            Status::Fail,
+            "snippet files",
+            e.to_string(),
+            "unset SNIPMAN_BACKEND or set it to json or sqlite",
+        ),
+    }
+}
+
+fn check_json_files() -> Check {
+    let dir = get_snippets_dir();
+    if !dir.is_dir() {
+        return Check::pass("snippet files", "no snippets yet");
+    }
+    match JsonDirStore::new(dir.clone()).unreadable() {
+        Ok(bad) if bad.is_empty() => Check::pass("snippet files", "all files load"),
+        Ok(bad) => Check::problem(
+            Status::Fail,
+            "snippet files",
+            bad.iter()
+                .map(|(path, e)| format!("{}: {}", path.display(), e))
+                .collect::<Vec<_>>()
+                .join("; "),
+            format!("repair these files or move them out of {}", dir.display()),
+        ),
+        Err(e) => Check::problem(
+            Status::Fail,
+            "snippet files",
+            format!("cannot read {}: {}", dir.display(), e),
+            format!("check permissions of {}", dir.display()),
+        ),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn check_database() -> Check {
+    use crate::library;
+
+    let db = library::root(library::active()).join("snippets.db");
+    if !db.is_file() {
+        return Check::pass("snippet database", "no snippets yet");
+    }
+    match store::sqlite::unreadable(&db) {
+        Ok(bad) if bad.is_empty() => Check::pass("snippet database", "all rows load"),
+        Ok(bad) => Check::problem(
+            Status::Fail,
+            "snippet database",
+            bad.iter()
+                .map(|(id, e)| format!("{}: {}", id, e))
+                .collect::<Vec<_>>()
+                .join("; "),
+            format!(
+                "these snippets are skipped; repair or delete their rows in {}",
+                db.display()
+            ),
+        ),
+        Err(e) => Check::problem(
+            Status::Fail,
+            "snippet database",
+            format!("cannot read {}: {}", db.display(), e),
+            format!("check permissions of {}", db.display()),
+        ),
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn check_database() -> Check {
+    Check::problem(
+        Status::Fail,
+        "snippet database",
+        "backend is sqlite but snipman was built without the `sqlite` feature",
+        "rebuild with `--features sqlite`, or run `snipman config set backend json`",
+    )
+}
+
+fn check_shared_libraries() -> Vec<Check> {
+    config::get()
+        .shared_libraries
+        .iter()
+        .map(|dir| {
+            if dir.is_dir() {
+                Check::pass("shared library", dir.display().to_string())
+            } else {
+                Check::problem(
+                    Status::Warn,
+                    "shared library",
+                    format!(
+                        "{} is not a directory; its snippets are not listed",
+                        dir.display()
+                    ),
+                    "mount the share, or remove the path from shared_libraries in the config file",
+                )
+            }
+        })
+        .collect()
+}
+
+/// Only reported when git storage is enabled.
+fn check_git() -> Option<Check> {
+    if !config::get().git.enabled {
+        return None;
+    }
+    Some(match find_program("git") {
+        Some(path) => Check::pass("git", path.display().to_string()),
+        None => Check::problem(
+            Status::Fail,
+            "git",
+            "git.enabled is set but git was not found on PATH",
+            "install git, or run `snipman config set git.enabled false`",
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_fix_below_problem() {
+        let check = Check::problem(Status::Warn, "MANPATH", "not set", "export it");
+        assert_eq!(
+            check.to_string(),
+            "[WARN] MANPATH: not set\n       fix: export it"
+        );
+    }
+}
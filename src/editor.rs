@@ -0,0 +1,146 @@
+//! External editor integration for composing and editing snippet bodies.
+
+use crate::config;
+use crate::os::OsKind;
+use std::process::{Command, Stdio};
+use std::{env, fs, io};
+
+/// The editor program and its leading arguments (the file path goes last).
+///
+/// Resolution order:
+/// - `editor` from the config file, then $VISUAL, then $EDITOR if set (parsed with a minimal shell-like splitter)
+/// - Windows: notepad.exe
+/// - macOS: `open -W -t`
+/// - Other Unix: prefers `nano` if available, otherwise `vi`
+pub fn editor_command() -> io::Result<(String, Vec<String>)> {
+    // Prefer the configured editor, then $VISUAL, then $EDITOR
+    let configured = config::get()
+        .editor
+        .clone()
+        .ok_or(env::VarError::NotPresent);
+    if let Ok(spec) = configured
+        .or_else(|_| env::var("VISUAL"))
+        .or_else(|_| env::var("EDITOR"))
+    {
+        let mut parts = parse_cmdline(&spec);
+        if parts.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty editor command",
+            ));
+        }
+        let prog = parts.remove(0);
+        return Ok((prog, parts));
+    }
+    let command = match OsKind::current() {
+        OsKind::Windows => ("notepad.exe".to_string(), Vec::new()),
+        OsKind::Macos => ("open".to_string(), vec!["-W".into(), "-t".into()]),
+        _ => {
+            let prefer_nano = Command::new("nano")
+                .arg("--version")
+                .stdout(Stdio::null())
+                .status()
+                .is_ok();
+            let prog = if prefer_nano { "nano" } else { "vi" };
+            (prog.to_string(), Vec::new())
+        }
+    };
+    Ok(command)
+}
+
+/// Open a text editor pre-filled with `initial` and return the edited contents.
+///
+/// The editor is chosen by [`editor_command`].
+///
+/// Returns the edited text, or an error if the editor fails to launch or exits non-zero.
+pub fn open_editor(initial: &str) -> io::Result<String> {
+    open_editor_with(initial, Stdio::inherit())
+}
+
+/// Like [`open_editor`], but with the editor's stdout connected to `stdout`
+/// (e.g. the terminal when our own stdout is a pipe).
+pub fn open_editor_with(initial: &str, stdout: Stdio) -> io::Result<String> {
+    let mut path = env::temp_dir();
+    path.push(format!("snipman_{}.txt", std::process::id()));
+    fs::write(&path, initial)?;
+
+    let (prog, args) = editor_command()?;
+    let mut cmd = Command::new(prog);
+    cmd.args(args).arg(&path);
+
+    let status = cmd.stdout(stdout).status()?;
+    if !status.success() {
+        return Err(io::Error::other("editor exited with non-zero status"));
+    }
+
+    let contents = fs::read_to_string(&path)?;
+    let _ = fs::remove_file(&path);
+    Ok(contents)
+}
+
+/// Minimal shell‑like splitter for `$VISUAL`/`$EDITOR`.
+///
+/// Splits a command string into argv without invoking a shell.
+///
+/// Behavior:
+/// - Whitespace outside quotes separates arguments.
+/// - Single quotes `'...'` take text literally; backslashes have no special meaning inside.
+/// - Double quotes `"..."` group text; backslash `\` escapes the next character inside.
+/// - Outside single quotes, a backslash `\` escapes the next character (including space and quotes).
+/// - Quote characters are not included in results unless escaped inside double quotes.
+/// - Unclosed quotes are tolerated: remaining text goes into the current token.
+/// - A trailing standalone backslash is ignored.
+///
+/// Not a full shell parser:
+/// - No variable expansion, globbing, pipelines, or command substitution.
+///
+/// # Examples:
+/// ```rust,ignore
+/// assert_eq!(parse_cmdline(r#"code -w"#), ["code", "-w"]);
+/// assert_eq!(parse_cmdline(r#"my\ editor --flag"#), ["my editor", "--flag"]);
+/// assert_eq!(parse_cmdline(r#"nvim "+set ft=rust""#), ["nvim", "+set ft=rust"]);
+/// assert_eq!(
+///     parse_cmdline(r#"sh -c "echo \"hi\" 'and bye'""#),
+///     ["sh", "-c", r#"echo "hi" 'and bye'"#]
+/// );
+/// assert_eq!(
+///     parse_cmdline(r#"--ext=\*.rs 'path with space'/file"#),
+///     ["--ext=*.rs", "path with space/file"]
+/// );
+/// ```
+pub fn parse_cmdline(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut buf = String::new();
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut escape = false;
+
+    for ch in s.chars() {
+        if escape {
+            buf.push(ch);
+            escape = false;
+            continue;
+        }
+        match ch {
+            '\\' if !in_single => {
+                escape = true;
+            }
+            '\'' if !in_double => {
+                in_single = !in_single;
+            }
+            '"' if !in_single => {
+                in_double = !in_double;
+            }
+            c if c.is_whitespace() && !in_single && !in_double => {
+                if !buf.is_empty() {
+                    args.push(std::mem::take(&mut buf));
+                }
+            }
+            _ => buf.push(ch),
+        }
+    }
+    if !buf.is_empty() {
+        args.push(buf);
+    }
+    args
+}
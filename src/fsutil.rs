@@ -0,0 +1,171 @@
+//! Crash-safe file writes, checksums and cross-process locking for the data directory,
+//! and program lookup through `$PATH`.
+
+use crate::os;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replace `path` with `contents` so readers only ever see the old or the new file.
+///
+/// The data is written to a temporary file in the same directory, flushed to disk,
+/// and renamed over the target. On Unix the directory is synced afterwards so the
+/// rename itself survives a crash.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let tmp = dir.join(format!(
+        ".{}.tmp{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return result;
+    }
+
+    if os::current_os().is_unix() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Move a file, copying it when `from` and `to` are on different filesystems.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Exclusive advisory lock on a lock file, released when dropped.
+///
+/// Every process mutating the snippet directory takes this lock first, so
+/// concurrent `snipman` invocations (e.g. the TUI and a script) serialize.
+pub struct DirLock {
+    file: File,
+}
+
+impl DirLock {
+    /// Block until the lock at `path` is acquired, creating the file if needed.
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        file.lock()?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Lowercase hex SHA-256 of `bytes`, used to notice files changed behind our back.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Lock file guarding the snippet directory `dir`: `.lock` in its parent (the data root).
+pub fn lock_path_for(dir: &Path) -> PathBuf {
+    dir.parent().unwrap_or(dir).join(".lock")
+}
+
+/// Resolve `program` like the OS would: paths as-is, bare names through $PATH.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        [program.to_string(), format!("{}.exe", program)]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// A fresh directory under the system temp dir for tests, removed with its
+/// contents when dropped (also when the test panics).
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("snipman_test_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_write_replaces_contents() {
+        let dir = TempDir::new();
+        let path = dir.join("a.json");
+
+        write_atomic(&path, b"one").unwrap();
+        write_atomic(&path, b"two").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let lock = DirLock::acquire(&dir.join(".lock")).unwrap();
+        drop(lock);
+    }
+
+    #[test]
+    fn unknown_programs_are_not_found() {
+        assert!(find_program("definitely-not-a-real-editor").is_none());
+    }
+}
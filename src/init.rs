@@ -1,26 +1,67 @@
+use crate::fsutil;
+use crate::keybindings;
 use crate::os::OsKind;
 use crate::shell::ShellTarget;
+use crate::snippets::{default_data_root, get_data_root};
 use crate::Cli;
 use clap::CommandFactory;
-use clap_complete::{generate_to, Shell};
+use clap_complete::{generate, Shell};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::process::Command as StdCommand;
 use std::{env, fs, io};
 
 pub mod state {
-    use crate::init::user_dirs;
+    use crate::fsutil::write_atomic;
+    use crate::init::{user_dirs, InstallOptions};
     use serde::{Deserialize, Serialize};
     use std::path::PathBuf;
     use std::{fs, io};
 
+    /// A file written by `install`, with the SHA-256 of what is on disk.
+    #[derive(Clone, Serialize, Deserialize)]
+    pub struct FileEntry {
+        pub path: PathBuf,
+        pub sha256: String,
+        /// The user's edit was kept instead of writing ours; later installs
+        /// keep it too unless forced.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub kept: bool,
+    }
+
+    /// A marked block `install` added to a shell rc file, with the SHA-256 of its body.
+    #[derive(Clone, Serialize, Deserialize)]
+    pub struct BlockEntry {
+        pub file: PathBuf,
+        pub marker: String,
+        pub sha256: String,
+        /// The user's edit of the body was kept, see [`FileEntry::kept`].
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub kept: bool,
+    }
+
     /// Persisted metadata written by `snipman install` to indicate that the
-    /// one-time initialization has completed.
+    /// one-time initialization has completed, and the manifest of everything it
+    /// wrote so later runs can detect drift, repair and uninstall precisely.
+    ///
+    /// Stamps written before the manifest existed only have `version` and
+    /// `installed_at_unix`; the other fields default to empty.
     #[derive(Serialize, Deserialize)]
-    struct InstallState {
+    pub struct InstallState {
         /// Package version at install time (from CARGO_PKG_VERSION)
-        version: String,
+        pub version: String,
         /// Unix epoch seconds when installation finished
-        installed_at_unix: u64,
+        pub installed_at_unix: u64,
+        /// Options `install` ran with
+        #[serde(default)]
+        pub options: Option<InstallOptions>,
+        /// Generated files (man page, completions)
+        #[serde(default)]
+        pub files: Vec<FileEntry>,
+        /// Blocks added to shell rc files
+        #[serde(default)]
+        pub rc_blocks: Vec<BlockEntry>,
     }
 
     /// Location of the JSON install-stamp file. Ensures the parent directory exists.
@@ -30,8 +71,24 @@ pub mod state {
         Ok(dirs.data_root.join("install_state.json"))
     }
 
-    /// Write the install-stamp with version and timestamp for gating.
-    pub fn write_install_stamp() -> io::Result<()> {
+    /// Read the install stamp, or `None` if `install` has not run.
+    pub fn read_install_state() -> io::Result<Option<InstallState>> {
+        let data = match fs::read_to_string(install_stamp_path()?) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        serde_json::from_str(&data)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the install-stamp with version, timestamp and manifest, atomically.
+    pub fn write_install_stamp(
+        options: &InstallOptions,
+        files: Vec<FileEntry>,
+        rc_blocks: Vec<BlockEntry>,
+    ) -> io::Result<()> {
         let stamp_path = install_stamp_path()?;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -40,10 +97,12 @@ pub mod state {
         let state = InstallState {
             version: env!("CARGO_PKG_VERSION").to_string(),
             installed_at_unix: now,
+            options: Some(options.clone()),
+            files,
+            rc_blocks,
         };
-        let json = serde_json::to_string_pretty(&state)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        fs::write(stamp_path, json)
+        let json = serde_json::to_string_pretty(&state).map_err(io::Error::other)?;
+        write_atomic(&stamp_path, json.as_bytes())
     }
 
     /// Return true if the one-time installation has completed.
@@ -54,52 +113,45 @@ pub mod state {
     }
 }
 
+/// Choices made on the `install` command line, recorded in the manifest.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct InstallOptions {
+    /// Shell(s) completions were generated for.
+    pub shell: ShellTarget,
+    /// Leave shell rc files alone (no MANPATH/fpath blocks).
+    pub no_modify_rc: bool,
+    /// Install the Ctrl-S picker widget.
+    pub keybindings: bool,
+}
+
 /// Convenience holder for user-specific directories used during installation.
 ///
 /// Notes (platform-specific):
-/// - data_root:
-///   - Linux: $XDG_DATA_HOME or ~/.local/share, joined with ".snipman"
-///   - macOS: ~/Library/Application Support/.snipman
-///   - Windows: %APPDATA% or %USERPROFILE%/AppData/Roaming, joined with ".snipman"
+/// - data_root: [`default_data_root`], where the install stamp lives; it ignores
+///   `SNIPMAN_DATA_DIR` and `data_dir`, which only move snippet data
 /// - config_root:
 ///   - Linux: $XDG_CONFIG_HOME or ~/.config/snipman
 ///   - macOS: ~/Library/Preferences/snipman
 ///   - Windows: %APPDATA%/snipman
-struct UserDirs {
-    home: PathBuf,
-    man1: PathBuf,
-    bash: PathBuf,
-    zsh: PathBuf,
-    fish: PathBuf,
-    data_root: PathBuf,
-    config_root: PathBuf,
+/// - zdotdir: $ZDOTDIR or the home directory, where `.zshrc` lives
+pub struct UserDirs {
+    pub home: PathBuf,
+    pub zdotdir: PathBuf,
+    pub man1: PathBuf,
+    pub bash: PathBuf,
+    pub zsh: PathBuf,
+    pub fish: PathBuf,
+    pub data_root: PathBuf,
+    pub config_root: PathBuf,
 }
 
 /// Derive the per-user directories used for installing assets and state.
-fn user_dirs() -> io::Result<UserDirs> {
+pub fn user_dirs() -> io::Result<UserDirs> {
     let home = env::var_os("HOME")
         .map(PathBuf::from)
         .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME not set"))?;
 
-    // Data root (mirrors snippets storage root on Linux/macOS/Windows)
-    let data_root = match OsKind::current() {
-        OsKind::Windows => env::var_os("APPDATA")
-            .map(PathBuf::from)
-            .or_else(|| {
-                env::var_os("USERPROFILE").map(|p| PathBuf::from(p).join("AppData").join("Roaming"))
-            })
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".snipman"),
-        OsKind::Macos => home
-            .join("Library")
-            .join("Application Support")
-            .join(".snipman"),
-        OsKind::Linux | OsKind::Unknown(_) => env::var_os("XDG_DATA_HOME")
-            .map(PathBuf::from)
-            .or_else(|| Some(home.join(".local").join("share")))
-            .unwrap()
-            .join(".snipman"),
-    };
+    let data_root = default_data_root();
 
     let config_root = match OsKind::current() {
         OsKind::Windows => env::var_os("APPDATA")
@@ -114,8 +166,13 @@ fn user_dirs() -> io::Result<UserDirs> {
             .join("snipman"),
     };
 
+    let zdotdir = env::var_os("ZDOTDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.clone());
+
     Ok(UserDirs {
         home: home.clone(),
+        zdotdir,
         man1: home.join(".local/share/man/man1"),
         bash: home.join(".local/share/bash-completion/completions"),
         zsh: home.join(".local/share/zsh/site-functions"),
@@ -140,10 +197,469 @@ fn ensure_block_in_file(file: &Path, marker: &str, body: &str) -> io::Result<()>
         contents.push('\n');
     }
     contents.push_str(&format!("\n{start}\n{body}\n{end}\n"));
-    fs::write(file, contents)
+    write_rc_file(file, &contents)
+}
+
+/// Remove the block delimited by `# BEGIN {marker} (snipman)` / `# END {marker} (snipman)`
+/// from a text file, along with the blank line that [`ensure_block_in_file`] put before it.
+///
+/// Returns true if a block was removed. A missing file or block is not an error.
+fn remove_block_from_file(file: &Path, marker: &str) -> io::Result<bool> {
+    let start = format!("# BEGIN {marker} (snipman)");
+    let end = format!("# END {marker} (snipman)");
+    let contents = match fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let Some(begin) = contents.find(&start) else {
+        return Ok(false);
+    };
+    let Some(stop) = contents[begin..].find(&end).map(|i| begin + i + end.len()) else {
+        return Ok(false);
+    };
+    let before = contents[..begin]
+        .strip_suffix('\n')
+        .unwrap_or(&contents[..begin]);
+    let after = contents[stop..]
+        .strip_prefix('\n')
+        .unwrap_or(&contents[stop..]);
+    write_rc_file(file, &format!("{before}{after}"))?;
+    Ok(true)
 }
 
-/// Install user-scoped assets (man page, shell completions) and write the install stamp.
+/// Replace a shell rc file atomically, so a crash never leaves it truncated.
+///
+/// A symlinked rc file (e.g. from a dotfiles repository) is written through to
+/// its target, and an existing file keeps its permissions.
+fn write_rc_file(file: &Path, contents: &str) -> io::Result<()> {
+    let target = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    let permissions = fs::metadata(&target).ok().map(|m| m.permissions());
+    fsutil::write_atomic(&target, contents.as_bytes())?;
+    if let Some(permissions) = permissions {
+        fs::set_permissions(&target, permissions)?;
+    }
+    Ok(())
+}
+
+/// Body of the `marker` block in `contents`, if there is one.
+fn block_body<'a>(contents: &'a str, marker: &str) -> Option<&'a str> {
+    let start = format!("# BEGIN {marker} (snipman)\n");
+    let end = format!("\n# END {marker} (snipman)");
+    let begin = contents.find(&start)? + start.len();
+    let len = contents[begin..].find(&end)?;
+    Some(&contents[begin..begin + len])
+}
+
+/// The rc file snipman edits for `shell`.
+pub fn rc_file(dirs: &UserDirs, shell: Shell) -> Option<PathBuf> {
+    match shell {
+        Shell::Bash => Some(dirs.home.join(".bashrc")),
+        Shell::Zsh => Some(dirs.zdotdir.join(".zshrc")),
+        Shell::Fish => Some(dirs.home.join(".config/fish/config.fish")),
+        _ => None,
+    }
+}
+
+/// Whether the Ctrl-S widget is currently installed: per the manifest, or for
+/// stamps without one, whether any rc file contains its block.
+pub fn keybindings_installed() -> bool {
+    if let Ok(Some(state)) = state::read_install_state()
+        && let Some(options) = state.options
+    {
+        return options.keybindings;
+    }
+    user_dirs().is_ok_and(|dirs| has_rc_block(&dirs, keybindings::MARKER))
+}
+
+/// Whether any rc file snipman edits contains the `marker` block.
+fn has_rc_block(dirs: &UserDirs, marker: &str) -> bool {
+    [Shell::Bash, Shell::Zsh, Shell::Fish]
+        .into_iter()
+        .filter_map(|sh| rc_file(dirs, sh))
+        .any(|rc| block_body(&fs::read_to_string(rc).unwrap_or_default(), marker).is_some())
+}
+
+/// Where the completion script for `shell` is installed.
+pub fn completion_path(dirs: &UserDirs, shell: Shell, bin_name: &str) -> Option<PathBuf> {
+    match shell {
+        Shell::Bash => Some(dirs.bash.join(bin_name)),
+        Shell::Zsh => Some(dirs.zsh.join(format!("_{bin_name}"))),
+        Shell::Fish => Some(dirs.fish.join(format!("{bin_name}.fish"))),
+        _ => None,
+    }
+}
+
+/// Markers of every rc-file block `install` may write.
+const RC_MARKERS: [&str; 3] = ["SNIPMAN_ZSH_FPATH", "SNIPMAN_MANPATH", keybindings::MARKER];
+
+/// Entries snipman creates in a data root. A root moved with `SNIPMAN_DATA_DIR`
+/// or `data_dir` may be shared with other files, so only these are purged there.
+const DATA_ROOT_ENTRIES: [&str; 5] = ["snippets", "libraries", "projects", "snippets.db", ".lock"];
+
+/// One thing `uninstall` removes.
+pub enum Removal {
+    /// A generated file (man page, completion script, install stamp).
+    File(PathBuf),
+    /// A marked block inside a shell rc file.
+    Block { file: PathBuf, marker: &'static str },
+    /// A directory and everything in it (snippet data, config).
+    Dir(PathBuf),
+    /// A directory that is only removed if nothing else is left in it.
+    EmptyDir(PathBuf),
+}
+
+impl fmt::Display for Removal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Removal::File(path) => write!(f, "file      {}", path.display()),
+            Removal::Block { file, marker } => {
+                write!(f, "rc block  {} in {}", marker, file.display())
+            }
+            Removal::Dir(path) => write!(f, "directory {}", path.display()),
+            Removal::EmptyDir(path) => write!(f, "directory {} (if empty)", path.display()),
+        }
+    }
+}
+
+impl Removal {
+    /// Remove the item. Returns false if it was left in place (a non-empty [`Removal::EmptyDir`]).
+    fn apply(&self) -> io::Result<bool> {
+        match self {
+            Removal::File(path) => fs::remove_file(path).map(|_| true),
+            Removal::Block { file, marker } => remove_block_from_file(file, marker).map(|_| true),
+            Removal::Dir(path) => fs::remove_dir_all(path).map(|_| true),
+            Removal::EmptyDir(path) => {
+                if fs::read_dir(path)?.next().is_some() {
+                    return Ok(false);
+                }
+                fs::remove_dir(path).map(|_| true)
+            }
+        }
+    }
+}
+
+/// Everything `install` left behind that still exists, in removal order.
+///
+/// With `purge`, also the data root (snippets, backups, databases) and the
+/// config root. From a data root moved by `SNIPMAN_DATA_DIR` or `data_dir`
+/// only the [entries snipman creates](DATA_ROOT_ENTRIES) are removed, and the
+/// directory itself only if nothing else is left in it.
+pub fn uninstall_plan(purge: bool) -> io::Result<Vec<Removal>> {
+    Ok(removals(
+        &user_dirs()?,
+        &get_data_root(),
+        state::read_install_state()?,
+        purge,
+    ))
+}
+
+/// [`uninstall_plan`] for the given directories, data root and install stamp.
+fn removals(
+    dirs: &UserDirs,
+    data_root: &Path,
+    installed: Option<state::InstallState>,
+    purge: bool,
+) -> Vec<Removal> {
+    let mut plan = Vec::new();
+
+    // Files from the manifest, plus the default locations for stamps without one.
+    let bin_name = Cli::command().get_name().to_string();
+    let mut files: Vec<PathBuf> = installed
+        .map(|s| s.files.into_iter().map(|f| f.path).collect())
+        .unwrap_or_default();
+    files.push(dirs.man1.join(format!("{bin_name}.1")));
+    files.push(dirs.bash.join(format!("{bin_name}.bash")));
+    files.extend(
+        [Shell::Bash, Shell::Zsh, Shell::Fish]
+            .into_iter()
+            .filter_map(|sh| completion_path(dirs, sh, &bin_name)),
+    );
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|p| p.is_file() && seen.insert(p.clone()));
+    plan.extend(files.into_iter().map(Removal::File));
+
+    for sh in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+        let Some(rc) = rc_file(dirs, sh) else {
+            continue;
+        };
+        let contents = fs::read_to_string(&rc).unwrap_or_default();
+        for marker in RC_MARKERS {
+            if contents.contains(&format!("# BEGIN {marker} (snipman)")) {
+                plan.push(Removal::Block {
+                    file: rc.clone(),
+                    marker,
+                });
+            }
+        }
+    }
+
+    if purge {
+        let roots = [&dirs.data_root, &dirs.config_root];
+        if !roots.iter().any(|root| data_root.starts_with(root)) {
+            for name in DATA_ROOT_ENTRIES {
+                let path = data_root.join(name);
+                if path.is_dir() {
+                    plan.push(Removal::Dir(path));
+                } else if path.is_file() {
+                    plan.push(Removal::File(path));
+                }
+            }
+            let contains_ours = roots
+                .iter()
+                .chain([&&dirs.home])
+                .any(|p| p.starts_with(data_root));
+            if data_root.is_dir() && !contains_ours {
+                plan.push(Removal::EmptyDir(data_root.to_path_buf()));
+            }
+        }
+        plan.extend(
+            roots
+                .into_iter()
+                .filter(|p| p.is_dir())
+                .map(|p| Removal::Dir(p.clone())),
+        );
+    } else {
+        let stamp = dirs.data_root.join("install_state.json");
+        if stamp.is_file() {
+            plan.push(Removal::File(stamp));
+        }
+    }
+    plan
+}
+
+/// Carry out an [`uninstall_plan`], continuing past failures.
+///
+/// Returns the number of items that could not be removed (each is reported on stderr).
+pub fn uninstall(plan: &[Removal]) -> usize {
+    let mut failed = 0;
+    for item in plan {
+        match item.apply() {
+            Ok(true) => println!("Removed {}", item),
+            Ok(false) => {
+                if let Removal::EmptyDir(path) = item {
+                    println!("Kept directory {} (not empty)", path.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to remove {}: {}", item, e);
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+/// How an installed item differs from the manifest.
+pub enum Drift {
+    /// It no longer exists.
+    Missing,
+    /// Its contents changed since `install` wrote it.
+    Modified,
+}
+
+/// One manifest entry that no longer matches the file system.
+pub struct DriftItem {
+    pub kind: Drift,
+    /// Human-readable description of the item.
+    pub item: String,
+}
+
+impl fmt::Display for DriftItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            Drift::Missing => "missing ",
+            Drift::Modified => "modified",
+        };
+        write!(f, "{} {}", kind, self.item)
+    }
+}
+
+/// Compare the install manifest against the file system.
+///
+/// # Errors
+/// `NotFound` if `install` has not run, `InvalidData` if the stamp predates the
+/// manifest (re-run `install` to record one).
+pub fn check_install() -> io::Result<Vec<DriftItem>> {
+    let state = state::read_install_state()?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "snipman is not installed"))?;
+    if state.options.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "install stamp has no manifest; run `snipman install` to record one",
+        ));
+    }
+    Ok(drift(&state))
+}
+
+/// Entries of the manifest in `state` that no longer match the file system.
+fn drift(state: &state::InstallState) -> Vec<DriftItem> {
+    let mut drift = Vec::new();
+    for entry in &state.files {
+        let kind = match fs::read(&entry.path) {
+            Ok(bytes) if fsutil::sha256_hex(&bytes) == entry.sha256 => continue,
+            Ok(_) => Drift::Modified,
+            Err(_) => Drift::Missing,
+        };
+        drift.push(DriftItem {
+            kind,
+            item: format!("file      {}", entry.path.display()),
+        });
+    }
+    for entry in &state.rc_blocks {
+        let contents = fs::read_to_string(&entry.file).unwrap_or_default();
+        let kind = match block_body(&contents, &entry.marker) {
+            Some(body) if fsutil::sha256_hex(body.as_bytes()) == entry.sha256 => continue,
+            Some(_) => Drift::Modified,
+            None => Drift::Missing,
+        };
+        drift.push(DriftItem {
+            kind,
+            item: format!("rc block  {} in {}", entry.marker, entry.file.display()),
+        });
+    }
+    drift
+}
+
+/// Writes install assets and records them for the manifest.
+///
+/// Anything the previous manifest lists with a different checksum than what is on
+/// disk, or as kept, was changed by the user; it is left alone (with a warning)
+/// unless `force`.
+struct Installer {
+    previous: Option<state::InstallState>,
+    force: bool,
+    files: Vec<state::FileEntry>,
+    rc_blocks: Vec<state::BlockEntry>,
+}
+
+impl Installer {
+    /// Kept user edits are recorded with their own checksum and marked `kept`.
+    fn write_file(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut sha256 = fsutil::sha256_hex(contents);
+        let mut kept = false;
+        match fs::read(path) {
+            Ok(current) if fsutil::sha256_hex(&current) == sha256 => {}
+            Ok(current) if !self.force && self.file_modified(path, &current) => {
+                eprintln!(
+                    "Keeping {} (modified since install; use --force to overwrite)",
+                    path.display()
+                );
+                sha256 = fsutil::sha256_hex(&current);
+                kept = true;
+            }
+            _ => fs::write(path, contents)?,
+        }
+        self.files.push(state::FileEntry {
+            path: path.to_path_buf(),
+            sha256,
+            kept,
+        });
+        Ok(())
+    }
+
+    /// Whether the previous manifest recorded `path` with different contents,
+    /// or as kept.
+    fn file_modified(&self, path: &Path, current: &[u8]) -> bool {
+        let Some(previous) = &self.previous else {
+            return false;
+        };
+        previous
+            .files
+            .iter()
+            .any(|f| f.path == path && (f.kept || f.sha256 != fsutil::sha256_hex(current)))
+    }
+
+    fn add_block(&mut self, file: &Path, marker: &str, body: &str) -> io::Result<()> {
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = fs::read_to_string(file).unwrap_or_default();
+        let mut sha256 = fsutil::sha256_hex(body.as_bytes());
+        let mut kept = false;
+        match block_body(&contents, marker) {
+            Some(existing) if existing == body => {}
+            Some(existing) if !self.force && !self.block_recorded(file, marker, existing) => {
+                eprintln!(
+                    "Keeping {} block in {} (modified since install; use --force to replace)",
+                    marker,
+                    file.display()
+                );
+                sha256 = fsutil::sha256_hex(existing.as_bytes());
+                kept = true;
+            }
+            Some(_) => {
+                remove_block_from_file(file, marker)?;
+                ensure_block_in_file(file, marker, body)?;
+            }
+            None => ensure_block_in_file(file, marker, body)?,
+        }
+        self.rc_blocks.push(state::BlockEntry {
+            file: file.to_path_buf(),
+            marker: marker.to_string(),
+            sha256,
+            kept,
+        });
+        Ok(())
+    }
+
+    /// Whether the previous manifest recorded this block with exactly `body`
+    /// and not as kept, i.e. the user has not touched it and it is safe to replace.
+    fn block_recorded(&self, file: &Path, marker: &str, body: &str) -> bool {
+        let Some(previous) = &self.previous else {
+            return false;
+        };
+        let sha256 = fsutil::sha256_hex(body.as_bytes());
+        previous
+            .rc_blocks
+            .iter()
+            .any(|b| b.file == file && b.marker == marker && !b.kept && b.sha256 == sha256)
+    }
+}
+
+/// Add the Ctrl-S widget to the rc file of each target shell, or with
+/// `keybindings` unset, remove it from every rc file: widgets installed for
+/// `--shell all` must go even when only one shell is targeted now.
+///
+/// With `no_modify_rc` no rc file is touched, even if the widget is carried
+/// over from an earlier install.
+fn install_keybindings(
+    installer: &mut Installer,
+    dirs: &UserDirs,
+    options: &InstallOptions,
+    quiet: bool,
+) -> io::Result<()> {
+    if options.no_modify_rc {
+        if !quiet && (options.keybindings || has_rc_block(dirs, keybindings::MARKER)) {
+            println!("Leaving keybindings in shell rc files alone (--no-modify-rc).");
+        }
+        return Ok(());
+    }
+    if !options.keybindings {
+        for sh in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            if let Some(rc) = rc_file(dirs, sh)
+                && remove_block_from_file(&rc, keybindings::MARKER)?
+                && !quiet
+            {
+                println!("Removed {:?} keybinding: {}", sh, rc.display());
+            }
+        }
+        return Ok(());
+    }
+    for sh in options.shell.to_shells() {
+        let (Some(rc), Some(widget)) = (rc_file(dirs, sh), keybindings::widget(sh)) else {
+            continue;
+        };
+        installer.add_block(&rc, keybindings::MARKER, widget)?;
+        if !quiet {
+            println!("Installed {:?} keybinding (Ctrl-S): {}", sh, rc.display());
+        }
+    }
+    Ok(())
+}
+
+/// Install user-scoped assets (man page, shell completions, optional keybindings)
+/// and write the install stamp with a manifest of everything written.
 ///
 /// What it does:
 /// - Creates the necessary directories under the user's home (man1, shell completion dirs, data/config roots).
@@ -151,15 +667,63 @@ fn ensure_block_in_file(file: &Path, marker: &str, body: &str) -> io::Result<()>
 /// - Best-effort refresh of the man database via `mandb -q` (ignored on failure).
 /// - Generates shell completions for the requested target(s) and writes them to conventional paths:
 ///   - Bash: ~/.local/share/bash-completion/completions/snipman
-///   - Zsh:  ~/.local/share/zsh/site-functions/_snipman (name determined by clap_complete)
+///   - Zsh:  ~/.local/share/zsh/site-functions/_snipman
 ///   - Fish: ~/.config/fish/completions/snipman.fish
-///   For Bash, a generated `*.bash` file is renamed to `snipman` for better autoloading.
 /// - If `no_modify_rc` is false and the detected shell is Zsh, appends a small block to $ZDOTDIR/.zshrc (or ~/.zshrc)
 ///   to ensure the zsh completion fpath is set and compinit is invoked. The block is idempotent.
-/// - Finally, writes a JSON stamp file under the data root to indicate initialization completed.
+/// - Adds the Ctrl-S picker widget to each target shell's rc file if `keybindings` is set, and removes it from every shell's rc file otherwise.
+/// - Finally, writes a JSON stamp file under the data root listing every file and rc block with its checksum.
+///
+/// Running it again repairs missing items. Items changed by the user since the
+/// last install are kept unless `force` is set.
 ///
 /// Returns an error only for unrecoverable filesystem operations or generation failures.
-pub fn install_user_assets(target: ShellTarget, no_modify_rc: bool) -> io::Result<()> {
+pub fn install_user_assets(options: &InstallOptions, force: bool) -> io::Result<()> {
+    install_assets(options, force, false)
+}
+
+/// Re-install assets if the install stamp was written by a different version.
+///
+/// Replays the [options](upgrade_options) `install` ran with, without
+/// printing progress, and keeps user-modified items. Returns the previously
+/// installed version if an upgrade happened.
+pub fn upgrade_if_needed() -> io::Result<Option<String>> {
+    let Some(previous) = state::read_install_state()? else {
+        return Ok(None);
+    };
+    if previous.version == env!("CARGO_PKG_VERSION") {
+        return Ok(None);
+    }
+    install_assets(&upgrade_options(&previous, &user_dirs()?), false, true)?;
+    Ok(Some(previous.version))
+}
+
+/// Options to replay on upgrade: those in the manifest, or for stamps written
+/// before it, what the rc files show. Those are only edited again if they
+/// already have a MANPATH or fpath block, so `--no-modify-rc` installs stay
+/// untouched.
+fn upgrade_options(previous: &state::InstallState, dirs: &UserDirs) -> InstallOptions {
+    if let Some(options) = &previous.options {
+        return options.clone();
+    }
+    InstallOptions {
+        no_modify_rc: !has_rc_block(dirs, "SNIPMAN_MANPATH")
+            && !has_rc_block(dirs, "SNIPMAN_ZSH_FPATH"),
+        keybindings: has_rc_block(dirs, keybindings::MARKER),
+        ..InstallOptions::default()
+    }
+}
+
+/// Version recorded in the install stamp, if it differs from this build.
+pub fn stale_install_version() -> Option<String> {
+    state::read_install_state()
+        .ok()
+        .flatten()
+        .map(|s| s.version)
+        .filter(|v| v != env!("CARGO_PKG_VERSION"))
+}
+
+fn install_assets(options: &InstallOptions, force: bool, quiet: bool) -> io::Result<()> {
     let dirs = user_dirs()?;
     // Ensure dirs
     fs::create_dir_all(&dirs.man1)?;
@@ -169,15 +733,18 @@ pub fn install_user_assets(target: ShellTarget, no_modify_rc: bool) -> io::Resul
     fs::create_dir_all(&dirs.data_root)?;
     fs::create_dir_all(&dirs.config_root)?;
 
+    let mut installer = Installer {
+        previous: state::read_install_state().ok().flatten(),
+        force,
+        files: Vec::new(),
+        rc_blocks: Vec::new(),
+    };
+
     // Man page
     let man_path = dirs.man1.join("snipman.1");
-    {
-        let cmd = Cli::command();
-        let man = clap_mangen::Man::new(cmd);
-        let mut file = fs::File::create(&man_path)?;
-        man.render(&mut file)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    }
+    let mut man = Vec::new();
+    clap_mangen::Man::new(Cli::command()).render(&mut man)?;
+    installer.write_file(&man_path, &man)?;
     // Refresh man DB quietly (best-effort)
     let _ = StdCommand::new("mandb")
         .args([
@@ -192,55 +759,49 @@ pub fn install_user_assets(target: ShellTarget, no_modify_rc: bool) -> io::Resul
     // Completions
     let mut cmd = Cli::command();
     let bin_name = cmd.get_name().to_string();
-    for sh in target.to_shells() {
-        let out_dir = match sh {
-            Shell::Bash => &dirs.bash,
-            Shell::Zsh => &dirs.zsh,
-            Shell::Fish => &dirs.fish,
-            _ => continue,
-        };
-        match generate_to(sh, &mut cmd, &bin_name, out_dir) {
-            Ok(path) => {
-                if sh == Shell::Bash && path.extension().map(|e| e == "bash").unwrap_or(false) {
-                    let target = out_dir.join("snipman");
-                    let _ = fs::rename(&path, &target);
-                    println!("Installed bash completion: {}", target.display());
-                } else {
-                    println!("Installed {:?} completion: {}", sh, path.display());
-                }
-            }
-            Err(e) => eprintln!("Failed to generate {:?} completion: {}", sh, e),
+    for sh in options.shell.to_shells() {
+        let Some(path) = completion_path(&dirs, sh, &bin_name) else {
+            continue;
+        };
+        let mut script = Vec::new();
+        generate(sh, &mut cmd, &bin_name, &mut script);
+        match installer.write_file(&path, &script) {
+            Ok(()) if !quiet => println!("Installed {:?} completion: {}", sh, path.display()),
+            Ok(()) => {}
+            Err(e) => eprintln!("Failed to write {:?} completion: {}", sh, e),
         }
     }
 
-    if !no_modify_rc {
+    if !options.no_modify_rc {
         match crate::shell::ShellTarget::detect() {
             Some(ShellTarget::Zsh) => {
                 // Zsh: ensure fpath and MANPATH
-                let zshrc = env::var_os("ZDOTDIR")
-                    .map(PathBuf::from)
-                    .unwrap_or_else(|| dirs.home.clone())
-                    .join(".zshrc");
+                let zshrc = rc_file(&dirs, Shell::Zsh).unwrap();
                 let fpath_block = format!(
                     "fpath+=({})\nautoload -Uz compinit\ncompinit -u",
                     dirs.zsh.to_string_lossy()
                 );
-                let _ = ensure_block_in_file(&zshrc, "SNIPMAN_ZSH_FPATH", &fpath_block);
+                let _ = installer.add_block(&zshrc, "SNIPMAN_ZSH_FPATH", &fpath_block);
 
                 let man_block = r#"export MANPATH="$HOME/.local/share/man:$MANPATH""#;
-                let _ = ensure_block_in_file(&zshrc, "SNIPMAN_MANPATH", man_block);
+                let _ = installer.add_block(&zshrc, "SNIPMAN_MANPATH", man_block);
             }
             Some(ShellTarget::Bash) => {
                 // Bash: ensure MANPATH
-                let bashrc = dirs.home.join(".bashrc");
+                let bashrc = rc_file(&dirs, Shell::Bash).unwrap();
                 let man_block = r#"export MANPATH="$HOME/.local/share/man:$MANPATH""#;
-                let _ = ensure_block_in_file(&bashrc, "SNIPMAN_MANPATH", man_block);
+                let _ = installer.add_block(&bashrc, "SNIPMAN_MANPATH", man_block);
             }
             _ => {}
         }
     }
 
-    state::write_install_stamp()?;
+    install_keybindings(&mut installer, &dirs, options, quiet)?;
+
+    state::write_install_stamp(options, installer.files, installer.rc_blocks)?;
+    if quiet {
+        return Ok(());
+    }
     println!("Installed man page: {}", man_path.display());
     println!(
         "Install stamp written to {}",
@@ -248,3 +809,354 @@ pub fn install_user_assets(target: ShellTarget, no_modify_rc: bool) -> io::Resul
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsutil::TempDir;
+
+    /// User directories with every location inside `home`.
+    fn dirs_in(home: &Path) -> UserDirs {
+        UserDirs {
+            home: home.to_path_buf(),
+            zdotdir: home.to_path_buf(),
+            man1: home.join(".local/share/man/man1"),
+            bash: home.join(".local/share/bash-completion/completions"),
+            zsh: home.join(".local/share/zsh/site-functions"),
+            fish: home.join(".config/fish/completions"),
+            data_root: home.join(".local/share/.snipman"),
+            config_root: home.join(".config/snipman"),
+        }
+    }
+
+    #[test]
+    fn upgrading_a_stamp_without_manifest_keeps_rc_files_alone() {
+        let home = TempDir::new();
+        let dirs = dirs_in(&home);
+        let stamp: state::InstallState =
+            serde_json::from_str(r#"{"version":"0.1.0","installed_at_unix":1}"#).unwrap();
+        let options = upgrade_options(&stamp, &dirs);
+        assert!(options.no_modify_rc);
+        assert!(!options.keybindings);
+
+        let bashrc = home.join(".bashrc");
+        ensure_block_in_file(&bashrc, "SNIPMAN_MANPATH", "export MANPATH").unwrap();
+        ensure_block_in_file(&bashrc, keybindings::MARKER, "bind").unwrap();
+        let options = upgrade_options(&stamp, &dirs);
+        assert!(!options.no_modify_rc);
+        assert!(options.keybindings);
+
+        let manifest = state::InstallState {
+            options: Some(InstallOptions {
+                no_modify_rc: true,
+                ..InstallOptions::default()
+            }),
+            ..stamp
+        };
+        let options = upgrade_options(&manifest, &dirs);
+        assert!(options.no_modify_rc);
+        assert!(!options.keybindings);
+    }
+
+    #[test]
+    fn uninstall_plan_lists_what_install_left() {
+        let home = TempDir::new();
+        let dirs = dirs_in(&home);
+        let man = dirs.man1.join("snipman.1");
+        let bash = dirs.bash.join("snipman");
+        let elsewhere = home.join("completions/snipman.fish");
+        let stamp = dirs.data_root.join("install_state.json");
+        for file in [&man, &bash, &elsewhere, &stamp] {
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, "").unwrap();
+        }
+        let bashrc = home.join(".bashrc");
+        fs::write(&bashrc, "alias ll='ls -l'\n").unwrap();
+        ensure_block_in_file(&bashrc, "SNIPMAN_MANPATH", "export MANPATH").unwrap();
+
+        let entry = |path: PathBuf| state::FileEntry {
+            path,
+            sha256: String::new(),
+            kept: false,
+        };
+        let installed = state::InstallState {
+            version: "0.1.0".into(),
+            installed_at_unix: 1,
+            options: Some(InstallOptions::default()),
+            files: vec![
+                entry(elsewhere.clone()),
+                entry(man.clone()),
+                entry(home.join("gone")),
+            ],
+            rc_blocks: Vec::new(),
+        };
+        let plan = removals(&dirs, &dirs.data_root, Some(installed), false);
+        let listed: Vec<String> = plan.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            listed,
+            [
+                format!("file      {}", elsewhere.display()),
+                format!("file      {}", man.display()),
+                format!("file      {}", bash.display()),
+                format!("rc block  SNIPMAN_MANPATH in {}", bashrc.display()),
+                format!("file      {}", stamp.display()),
+            ]
+        );
+
+        assert_eq!(uninstall(&plan), 0);
+        assert_eq!(fs::read_to_string(&bashrc).unwrap(), "alias ll='ls -l'\n");
+        assert!(removals(&dirs, &dirs.data_root, None, false).is_empty());
+        assert!(dirs.data_root.is_dir());
+    }
+
+    /// A manifest recording `files` and `rc_blocks` as `(path, contents)` pairs.
+    fn manifest(files: &[(&Path, &str)], rc_blocks: &[(&Path, &str, &str)]) -> state::InstallState {
+        state::InstallState {
+            version: "0.1.0".into(),
+            installed_at_unix: 1,
+            options: Some(InstallOptions::default()),
+            files: files
+                .iter()
+                .map(|(path, contents)| state::FileEntry {
+                    path: path.to_path_buf(),
+                    sha256: fsutil::sha256_hex(contents.as_bytes()),
+                    kept: false,
+                })
+                .collect(),
+            rc_blocks: rc_blocks
+                .iter()
+                .map(|(file, marker, body)| state::BlockEntry {
+                    file: file.to_path_buf(),
+                    marker: marker.to_string(),
+                    sha256: fsutil::sha256_hex(body.as_bytes()),
+                    kept: false,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn drift_reports_modified_and_missing_items() {
+        let home = TempDir::new();
+        let same = home.join("snipman.1");
+        let edited = home.join("snipman.bash");
+        let gone = home.join("snipman.fish");
+        let bashrc = home.join(".bashrc");
+        fs::write(&same, "man").unwrap();
+        fs::write(&edited, "complete -F mine").unwrap();
+        ensure_block_in_file(&bashrc, "SNIPMAN_MANPATH", "export MANPATH=mine").unwrap();
+
+        let installed = manifest(
+            &[
+                (&same, "man"),
+                (&edited, "complete -F _snipman"),
+                (&gone, ""),
+            ],
+            &[
+                (&bashrc, "SNIPMAN_MANPATH", "export MANPATH"),
+                (&bashrc, keybindings::MARKER, "bind"),
+            ],
+        );
+        let report: Vec<String> = drift(&installed).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            report,
+            [
+                format!("modified file      {}", edited.display()),
+                format!("missing  file      {}", gone.display()),
+                format!("modified rc block  SNIPMAN_MANPATH in {}", bashrc.display()),
+                format!(
+                    "missing  rc block  {} in {}",
+                    keybindings::MARKER,
+                    bashrc.display()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn installer_keeps_user_edits_unless_forced() {
+        let home = TempDir::new();
+        let man = home.join("snipman.1");
+        let bashrc = home.join(".bashrc");
+        let previous = || {
+            Some(manifest(
+                &[(&man, "old")],
+                &[(&bashrc, "SNIPMAN_MANPATH", "old block")],
+            ))
+        };
+        let installer = |force| Installer {
+            previous: previous(),
+            force,
+            files: Vec::new(),
+            rc_blocks: Vec::new(),
+        };
+
+        fs::write(&man, "edited").unwrap();
+        ensure_block_in_file(&bashrc, "SNIPMAN_MANPATH", "edited block").unwrap();
+        let mut kept = installer(false);
+        kept.write_file(&man, b"new").unwrap();
+        kept.add_block(&bashrc, "SNIPMAN_MANPATH", "new block")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&man).unwrap(), "edited");
+        let rc = fs::read_to_string(&bashrc).unwrap();
+        assert_eq!(block_body(&rc, "SNIPMAN_MANPATH"), Some("edited block"));
+        // The manifest records the kept edits, so they are not drift and stay kept.
+        assert!(kept.files[0].kept);
+        assert_eq!(kept.files[0].sha256, fsutil::sha256_hex(b"edited"));
+        assert!(kept.rc_blocks[0].kept);
+        assert_eq!(
+            kept.rc_blocks[0].sha256,
+            fsutil::sha256_hex(b"edited block")
+        );
+        let recorded = state::InstallState {
+            files: kept.files,
+            rc_blocks: kept.rc_blocks,
+            ..manifest(&[], &[])
+        };
+        assert!(drift(&recorded).is_empty());
+        let mut again = Installer {
+            previous: Some(recorded),
+            ..installer(false)
+        };
+        again.write_file(&man, b"new").unwrap();
+        again
+            .add_block(&bashrc, "SNIPMAN_MANPATH", "new block")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&man).unwrap(), "edited");
+        let rc = fs::read_to_string(&bashrc).unwrap();
+        assert_eq!(block_body(&rc, "SNIPMAN_MANPATH"), Some("edited block"));
+
+        let mut forced = installer(true);
+        forced.write_file(&man, b"new").unwrap();
+        forced
+            .add_block(&bashrc, "SNIPMAN_MANPATH", "new block")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&man).unwrap(), "new");
+        let rc = fs::read_to_string(&bashrc).unwrap();
+        assert_eq!(block_body(&rc, "SNIPMAN_MANPATH"), Some("new block"));
+
+        // Items still as installed are updated without --force.
+        fs::write(&man, "old").unwrap();
+        installer(false).write_file(&man, b"newer").unwrap();
+        assert_eq!(fs::read_to_string(&man).unwrap(), "newer");
+    }
+
+    #[test]
+    fn purging_a_moved_data_root_keeps_other_files() {
+        let home = TempDir::new();
+        let dirs = dirs_in(&home);
+        fs::create_dir_all(&dirs.data_root).unwrap();
+        fs::create_dir_all(&dirs.config_root).unwrap();
+        let moved = home.join("Documents");
+        fs::create_dir_all(moved.join("snippets")).unwrap();
+        fs::write(moved.join("snippets.db"), "").unwrap();
+        fs::write(moved.join("notes.txt"), "mine").unwrap();
+
+        let plan: Vec<String> = removals(&dirs, &moved, None, true)
+            .iter()
+            .filter(|item| !matches!(item, Removal::Block { .. }))
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            plan,
+            [
+                format!("directory {}", moved.join("snippets").display()),
+                format!("file      {}", moved.join("snippets.db").display()),
+                format!("directory {} (if empty)", moved.display()),
+                format!("directory {}", dirs.data_root.display()),
+                format!("directory {}", dirs.config_root.display()),
+            ]
+        );
+
+        assert_eq!(uninstall(&removals(&dirs, &moved, None, true)), 0);
+        assert_eq!(fs::read_to_string(moved.join("notes.txt")).unwrap(), "mine");
+        assert_eq!(fs::read_dir(&moved).unwrap().count(), 1);
+        fs::remove_file(moved.join("notes.txt")).unwrap();
+        fs::create_dir(moved.join("projects")).unwrap();
+        assert_eq!(uninstall(&removals(&dirs, &moved, None, true)), 0);
+        assert!(!moved.exists());
+    }
+
+    #[test]
+    fn blocks_are_added_once_and_removed_cleanly() {
+        let home = TempDir::new();
+        let file = home.join(".bashrc");
+        fs::write(&file, "alias ll='ls -l'\n").unwrap();
+
+        ensure_block_in_file(&file, "TEST", "echo hi").unwrap();
+        ensure_block_in_file(&file, "TEST", "echo hi").unwrap();
+        let contents = fs::read_to_string(&file).unwrap();
+        assert_eq!(contents.matches("# BEGIN TEST (snipman)").count(), 1);
+        assert_eq!(block_body(&contents, "TEST"), Some("echo hi"));
+        assert_eq!(block_body(&contents, "OTHER"), None);
+
+        assert!(remove_block_from_file(&file, "TEST").unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "alias ll='ls -l'\n");
+        assert!(!remove_block_from_file(&file, "TEST").unwrap());
+
+        #[cfg(unix)]
+        {
+            let link = home.join(".zshrc");
+            std::os::unix::fs::symlink(&file, &link).unwrap();
+            ensure_block_in_file(&link, "TEST", "echo hi").unwrap();
+            assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+            assert_eq!(
+                block_body(&fs::read_to_string(&file).unwrap(), "TEST"),
+                Some("echo hi")
+            );
+        }
+    }
+
+    #[test]
+    fn removing_keybindings_cleans_every_shell() {
+        let home = TempDir::new();
+        let dirs = dirs_in(&home);
+        let mut installer = Installer {
+            previous: None,
+            force: false,
+            files: Vec::new(),
+            rc_blocks: Vec::new(),
+        };
+        let mut options = InstallOptions {
+            shell: ShellTarget::All,
+            keybindings: true,
+            ..InstallOptions::default()
+        };
+        install_keybindings(&mut installer, &dirs, &options, true).unwrap();
+        assert_eq!(installer.rc_blocks.len(), 3);
+        assert!(has_rc_block(&dirs, keybindings::MARKER));
+
+        options.shell = ShellTarget::Bash;
+        options.keybindings = false;
+        install_keybindings(&mut installer, &dirs, &options, true).unwrap();
+        assert!(!has_rc_block(&dirs, keybindings::MARKER));
+    }
+
+    #[test]
+    fn no_modify_rc_leaves_installed_keybindings_alone() {
+        let home = TempDir::new();
+        let dirs = dirs_in(&home);
+        let mut installer = Installer {
+            previous: None,
+            force: false,
+            files: Vec::new(),
+            rc_blocks: Vec::new(),
+        };
+        let mut options = InstallOptions {
+            shell: ShellTarget::Bash,
+            keybindings: true,
+            ..InstallOptions::default()
+        };
+        install_keybindings(&mut installer, &dirs, &options, true).unwrap();
+        let bashrc = rc_file(&dirs, Shell::Bash).unwrap();
+        let installed = fs::read(&bashrc).unwrap();
+
+        options.no_modify_rc = true;
+        options.shell = ShellTarget::All;
+        install_keybindings(&mut installer, &dirs, &options, true).unwrap();
+        options.keybindings = false;
+        install_keybindings(&mut installer, &dirs, &options, true).unwrap();
+        assert_eq!(fs::read(&bashrc).unwrap(), installed);
+        assert!(!rc_file(&dirs, Shell::Zsh).unwrap().exists());
+    }
+}
@@ -0,0 +1,55 @@
+//! Shell keybinding widgets that open the picker and insert the chosen snippet
+//! into the command line being edited.
+//!
+//! Each widget binds Ctrl-S, runs `snipman interactive --print` and splices its
+//! output in at the cursor. Ctrl-S normally stops terminal output (XON/XOFF flow
+//! control), so the widgets turn that off for interactive shells.
+
+use clap_complete::Shell;
+
+/// Marker of the rc-file block holding the widget (see `init::ensure_block_in_file`).
+pub const MARKER: &str = "SNIPMAN_KEYBINDINGS";
+
+const BASH: &str = r#"if [[ $- == *i* ]]; then
+  stty -ixon 2>/dev/null
+  __snipman_widget() {
+    local selected
+    selected="$(snipman interactive --print)" || return
+    READLINE_LINE="${READLINE_LINE:0:READLINE_POINT}${selected}${READLINE_LINE:READLINE_POINT}"
+    READLINE_POINT=$((READLINE_POINT + ${#selected}))
+  }
+  bind -m emacs-standard -x '"\C-s": __snipman_widget'
+  bind -m vi-insert -x '"\C-s": __snipman_widget'
+fi"#;
+
+const ZSH: &str = r#"if [[ -o interactive ]]; then
+  setopt no_flow_control
+  __snipman_widget() {
+    local selected
+    selected="$(snipman interactive --print </dev/tty)" && LBUFFER+="$selected"
+    zle reset-prompt
+  }
+  zle -N __snipman_widget
+  bindkey -M emacs '^S' __snipman_widget
+  bindkey -M viins '^S' __snipman_widget
+fi"#;
+
+const FISH: &str = r#"if status is-interactive
+    function __snipman_widget
+        set -l selected (snipman interactive --print | string collect)
+        and commandline -i -- $selected
+        commandline -f repaint
+    end
+    bind \cs __snipman_widget
+    bind -M insert \cs __snipman_widget
+end"#;
+
+/// Widget script for `shell`, or `None` if the shell is not supported.
+pub fn widget(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Bash => Some(BASH),
+        Shell::Zsh => Some(ZSH),
+        Shell::Fish => Some(FISH),
+        _ => None,
+    }
+}
@@ -0,0 +1,149 @@
+//! Named snippet libraries, e.g. separate `work` and `personal` collections.
+//!
+//! The `default` library is the data root itself, so snippets saved before
+//! libraries existed stay where they are. Every other library is a directory
+//! under `<data root>/libraries/<name>` with the same layout (a `snippets`
+//! directory, plus `snippets.db` for the sqlite backend).
+//!
+//! The library used by a command is chosen with `--library` (see [`select`]).
+
+use crate::snippets::get_data_root;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::{fs, io};
+
+/// Name of the library stored directly in the data root.
+pub const DEFAULT: &str = "default";
+
+static ACTIVE: OnceLock<String> = OnceLock::new();
+
+/// Make `name` the library returned by [`active`]. Only the first call has an effect.
+pub fn select(name: String) {
+    let _ = ACTIVE.set(name);
+}
+
+/// The library chosen at startup, or [`DEFAULT`].
+pub fn active() -> &'static str {
+    ACTIVE.get().map_or(DEFAULT, String::as_str)
+}
+
+/// Directory holding the data of library `name`.
+pub fn root(name: &str) -> PathBuf {
+    if name == DEFAULT {
+        get_data_root()
+    } else {
+        get_data_root().join("libraries").join(name)
+    }
+}
+
+/// Whether library `name` exists. The default library always does.
+pub fn exists(name: &str) -> bool {
+    name == DEFAULT || root(name).is_dir()
+}
+
+/// Names of all libraries, [`DEFAULT`] first, the rest sorted.
+pub fn list() -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    match fs::read_dir(get_data_root().join("libraries")) {
+        Ok(entries) => {
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_type()?.is_dir()
+                    && let Some(name) = entry.file_name().to_str()
+                    && validate_name(name).is_ok()
+                {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    names.sort();
+    names.insert(0, DEFAULT.to_string());
+    Ok(names)
+}
+
+/// Check that `name` can be used as a library directory name.
+///
+/// # Errors
+/// `InvalidInput` unless `name` is made of letters, digits, `-`, `_` and `.`
+/// and does not start with `.`.
+pub fn validate_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid library name '{}' (use letters, digits, '-', '_' and '.')",
+                name
+            ),
+        ))
+    }
+}
+
+/// Create an empty library and return its directory.
+///
+/// # Errors
+/// `AlreadyExists` if the library exists, `InvalidInput` for invalid names.
+pub fn create(name: &str) -> io::Result<PathBuf> {
+    validate_name(name)?;
+    if exists(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("library '{}' already exists", name),
+        ));
+    }
+    let dir = root(name);
+    fs::create_dir_all(dir.join("snippets"))?;
+    Ok(dir)
+}
+
+/// Delete a library and every snippet in it.
+///
+/// # Errors
+/// `InvalidInput` for the default library, `NotFound` if it does not exist.
+pub fn remove(name: &str) -> io::Result<()> {
+    validate_name(name)?;
+    if name == DEFAULT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the default library cannot be removed",
+        ));
+    }
+    if !exists(name) {
+        return Err(not_found(name));
+    }
+    fs::remove_dir_all(root(name))
+}
+
+/// Error for a library that does not exist, pointing at `library create`.
+pub fn not_found(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "library '{}' does not exist (create it with `snipman library create {}`)",
+            name, name
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_cannot_escape_the_libraries_directory() {
+        assert!(validate_name("work").is_ok());
+        assert!(validate_name("team.infra-2").is_ok());
+        for bad in ["", ".", "..", ".hidden", "a/b", "a\\b", "with space"] {
+            assert!(validate_name(bad).is_err(), "{:?} accepted", bad);
+        }
+    }
+}
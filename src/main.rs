@@ -2,23 +2,53 @@
 //!
 //! Commands:
 //! - add: create a new snippet with description, tags, and code
-//! - list: print all saved snippets
-//! - interactive: open the interactive TUI to fuzzy-search and copy a snippet
+//! - edit: change an existing snippet's fields or body, keeping its id
+//! - fork: copy a read-only shared snippet into your library
+//! - get/show: print one snippet's code or copy it to the clipboard
+//! - run: execute a snippet in the user's shell after confirmation
+//! - history/diff/restore: list, compare and roll back to earlier versions of a snippet
+//! - list: print all saved snippets (optionally filtered by a query)
+//! - search: fuzzy-search snippets with scores (human, JSON or TSV output)
+//! - remove/trash: delete snippets into the trash, then list, restore or empty it
+//! - migrate: upgrade stored snippets to the current schema version
+//! - sync: pull and push the git-backed snippet directory
+//! - doctor: check the installation and environment, suggesting fixes
+//! - config: show or change settings in config.toml
+//! - library: list, create or remove named snippet libraries
+//! - uninstall: remove installed assets, optionally with all snippet data
+//! - interactive: open the interactive TUI to fuzzy-search, copy (or print) and edit snippets
 
+mod clipboard;
+mod config;
+mod diff;
+mod doctor;
+mod editor;
+mod fsutil;
 mod init;
+mod keybindings;
+mod library;
+mod migrate;
 mod os;
+mod project;
+mod runner;
+mod search;
+mod select;
 mod shell;
 mod snippets;
+mod store;
+mod template;
 mod tui;
 
-use crate::os::OsKind;
+use crate::editor::open_editor;
+use crate::select::Selection;
 use crate::shell::ShellTarget;
-use crate::snippets::{load_snippets, save_snippet, Snippet};
+use crate::snippets::{Origin, Snippet, SortKey};
+use crate::store::SnippetStore;
 use clap::{Parser, Subcommand};
-use std::io::Read;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read};
 use std::path::PathBuf;
-use std::process::Command;
-use std::{env, fs, io};
+use std::{fs, io};
 
 /// Command-line interface for Snipman.
 #[derive(Parser)]
@@ -27,6 +57,13 @@ struct Cli {
     /// Subcommand to execute
     #[command(subcommand)]
     command: Commands,
+    /// Do not refresh man page and completions automatically after a version upgrade
+    /// (also: SNIPMAN_NO_AUTO_UPGRADE=1)
+    #[arg(long, global = true)]
+    no_auto_upgrade: bool,
+    /// Use this snippet library instead of the default one (see `snipman library list`)
+    #[arg(long, global = true, value_name = "NAME")]
+    library: Option<String>,
 }
 
 /// Available subcommands.
@@ -57,17 +94,169 @@ enum Commands {
         /// Open editor to write the snippet body
         #[arg(long)]
         editor: bool,
+
+        /// Language of the snippet body, e.g. "rust" or "bash"
+        #[arg(short, long)]
+        language: Option<String>,
+
+        /// Where the snippet came from (URL or file path)
+        #[arg(long)]
+        source: Option<String>,
+
+        /// Free-form notes shown alongside the snippet
+        #[arg(long)]
+        notes: Option<String>,
+
+        /// Save to the project's `.snipman/` directory instead of your library
+        #[arg(long)]
+        local: bool,
     },
-    /// List all snippets
-    List,
-    /// Remove the given snippet by its description
+    /// Edit an existing snippet, keeping its id
+    ///
+    /// Without any field flags, opens the body in $VISUAL/$EDITOR.
+    Edit {
+        /// Snippet id, exact description, or fuzzy query selecting the snippet
+        target: String,
+
+        /// New description
+        #[arg(short, long)]
+        description: Option<String>,
+
+        /// New comma-separated tags (replaces the existing ones)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+
+        /// New language
+        #[arg(short, long)]
+        language: Option<String>,
+
+        /// New source URL or file path
+        #[arg(long)]
+        source: Option<String>,
+
+        /// New notes
+        #[arg(long)]
+        notes: Option<String>,
+
+        /// Open the body in the editor, pre-filled with the current code
+        #[arg(long)]
+        editor: bool,
+    },
+    /// Copy a snippet from a read-only shared library into yours so it can be changed
+    ///
+    /// The copy keeps the snippet's id and takes precedence over the shared one.
+    Fork {
+        /// Snippet id, exact description, or fuzzy query selecting the snippet
+        target: String,
+    },
+    /// Print a single snippet's code, or copy it to the clipboard
+    #[command(visible_alias = "show")]
+    Get {
+        /// Snippet id, exact description, or fuzzy query selecting the snippet
+        target: String,
+
+        /// Copy the code to the clipboard instead of printing it
+        #[arg(short, long)]
+        copy: bool,
+
+        /// Value for a `{{placeholder}}` in the body, as NAME=VALUE (repeatable)
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = template::parse_var)]
+        vars: Vec<(String, String)>,
+
+        /// Output the body as stored, without substituting placeholders
+        #[arg(long, conflicts_with = "vars")]
+        raw: bool,
+    },
+    /// Run a snippet as a command in your shell, after confirmation
+    Run {
+        /// Snippet id, exact description, or fuzzy query selecting the snippet
+        target: String,
+
+        /// Value for a `{{placeholder}}` in the body, as NAME=VALUE (repeatable)
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = template::parse_var)]
+        vars: Vec<(String, String)>,
+
+        /// Run without asking for confirmation or prompting for placeholder values
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List the earlier versions of a snippet kept by every edit
+    History {
+        /// Snippet id, exact description, or fuzzy query selecting the snippet
+        target: String,
+    },
+    /// Show what changed between an earlier revision and the current version
+    Diff {
+        /// Snippet id, exact description, or fuzzy query selecting the snippet
+        target: String,
+        /// Revision number, as shown by `history`
+        rev: u32,
+    },
+    /// Roll a snippet back to an earlier revision; the current version stays in the history
+    Restore {
+        /// Snippet id, exact description, or fuzzy query selecting the snippet
+        target: String,
+        /// Revision number, as shown by `history`
+        rev: u32,
+    },
+    /// List all snippets, optionally only those matching a query
+    List {
+        /// Only list snippets whose description, tags or code contain this text
+        query: Option<String>,
+
+        /// Order of the listed snippets
+        #[arg(short, long, value_enum)]
+        sort: Option<SortKey>,
+    },
+    /// Fuzzy-search snippets, best matches first, with scores
+    ///
+    /// Uses the backend's index to narrow candidates when available.
+    Search {
+        /// Text to search for in descriptions, tags and code
+        query: String,
+
+        /// Show at most this many results
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+
+        /// Only include snippets carrying all of these tags (comma-separated or repeated)
+        #[arg(short, long, value_delimiter = ',')]
+        tag: Vec<String>,
+
+        /// Print results as a JSON array (includes the code)
+        #[arg(long, conflicts_with = "tsv")]
+        json: bool,
+
+        /// Print tab-separated `score, id, description, tags, language` lines
+        #[arg(long)]
+        tsv: bool,
+    },
+    /// Remove the given snippet by its description (it is kept in the trash for a while)
     Remove {
         /// The description of the snippet to remove
         #[arg(short, long)]
         description: String,
     },
+    /// List, restore or permanently delete removed snippets
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Upgrade stored snippets to the current schema version (originals are backed up)
+    Migrate {
+        /// Only report what would be upgraded, without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Enter the interactive TUI to search, copy and remove snippets
-    Interactive,
+    Interactive {
+        /// Where the chosen snippet goes; with `stdout` the TUI is drawn on the terminal device
+        #[arg(long, value_enum, default_value_t = tui::Output::Clipboard)]
+        output: tui::Output,
+        /// Print the chosen snippet to stdout (same as `--output stdout`), e.g. `$(snipman interactive --print)`
+        #[arg(long)]
+        print: bool,
+    },
 
     /// Install man page and shell completions into user directories and mark as installed
     Install {
@@ -77,11 +266,69 @@ enum Commands {
         /// Do not modify shell rc files (e.g., zsh fpath)
         #[arg(long)]
         no_modify_rc: bool,
+        /// Also bind Ctrl-S in the shell to open the picker and insert the chosen snippet
+        #[arg(long, conflicts_with_all = ["no_modify_rc", "remove_keybindings"])]
+        keybindings: bool,
+        /// Remove previously installed keybindings from the shell rc files
+        #[arg(long, conflicts_with = "no_modify_rc")]
+        remove_keybindings: bool,
+        /// Compare installed files and rc blocks against the install manifest and report drift
+        #[arg(long, conflicts_with_all = ["keybindings", "remove_keybindings", "force"])]
+        check: bool,
+        /// Overwrite files and rc blocks even if they were modified since the last install
+        #[arg(long)]
+        force: bool,
+    },
+    /// Pull snippets from the git remote and push local changes (needs `git.enabled`)
+    ///
+    /// Snippets changed on both sides are shown one at a time to choose which version to keep.
+    Sync {
+        /// Remote URL to use, saved as the repository's `origin` (default: git.remote)
+        #[arg(long)]
+        remote: Option<String>,
+        /// Settle every conflict this way instead of asking
+        #[arg(long, value_enum)]
+        prefer: Option<store::git::Resolution>,
+    },
+    /// Check the installation and environment and suggest fixes for problems
+    Doctor,
+    /// Show or change settings in config.toml
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Manage named snippet libraries, e.g. to keep work and personal snippets apart
+    Library {
+        #[command(subcommand)]
+        action: LibraryAction,
+    },
+    /// Remove everything `install` set up: man page, completions, rc-file blocks and the install stamp
+    Uninstall {
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+        /// Also delete all snippet data and configuration (asks for confirmation)
+        #[arg(long)]
+        purge: bool,
+        /// Do not ask for confirmation before purging
+        #[arg(short, long)]
+        yes: bool,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
+    match config::load() {
+        Ok(loaded) => config::init(loaded),
+        Err(e) => eprintln!("warning: ignoring invalid config file: {}", e),
+    }
+    if let Some(name) = cli.library {
+        if let Err(e) = library::validate_name(&name) {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        library::select(name);
+    }
 
     if requires_install_gate(&cli.command) && !init::state::is_installed() {
         eprintln!(
@@ -90,6 +337,9 @@ fn main() {
         );
         std::process::exit(2);
     }
+    if requires_install_gate(&cli.command) {
+        refresh_stale_install(cli.no_auto_upgrade || !config::get().auto_upgrade);
+    }
 
     match cli.command {
         Commands::Add {
@@ -99,6 +349,10 @@ fn main() {
             file,
             stdin,
             editor,
+            language,
+            source,
+            notes,
+            local,
         } => {
             let code_body = match resolve_code_input(code, file, stdin, editor) {
                 Ok(s) => s,
@@ -111,52 +365,339 @@ fn main() {
                 }
             };
 
-            let new_snippet = Snippet::new(description, tags, code_body);
-            if let Err(e) = save_snippet(&new_snippet) {
-                eprintln!("Error saving snippet: {}", e);
+            let mut new_snippet = Snippet::new(description, tags, code_body);
+            new_snippet.language = language;
+            new_snippet.source = source;
+            new_snippet.notes = notes;
+            let saved = if local {
+                store::open_project().and_then(|mut store| store.put(&new_snippet))
+            } else {
+                open_store_or_exit().put(&new_snippet)
+            };
+            match saved {
+                Ok(()) => println!("Snippet '{}' saved successfully!", new_snippet.description),
+                Err(e) => eprintln!("Error saving snippet: {}", e),
             }
         }
-        Commands::List => match load_snippets() {
-            Ok(snippets) => {
-                if snippets.is_empty() {
-                    println!("No snippets found.");
-                } else {
-                    println!("Found {} snippets:", snippets.len());
-                    for snippet in snippets {
-                        println!("- {} (Tags: {:?})", snippet.description, snippet.tags);
+        Commands::Edit {
+            target,
+            description,
+            tags,
+            language,
+            source,
+            notes,
+            editor,
+        } => {
+            let mut store = open_store_or_exit();
+            let mut snippet = select_or_exit(store.as_ref(), &target);
+            if snippet.origin == Origin::Shared {
+                eprintln!(
+                    "'{}' is in a read-only shared library. Run `snipman fork` to copy it into yours first.",
+                    snippet.description
+                );
+                std::process::exit(1);
+            }
+            let original = serde_json::to_value(&snippet).ok();
+            let no_field_flags = description.is_none()
+                && tags.is_none()
+                && language.is_none()
+                && source.is_none()
+                && notes.is_none();
+
+            if let Some(description) = description {
+                snippet.description = description;
+            }
+            if let Some(tags) = tags {
+                snippet.tags = tags;
+            }
+            if language.is_some() {
+                snippet.language = language;
+            }
+            if source.is_some() {
+                snippet.source = source;
+            }
+            if notes.is_some() {
+                snippet.notes = notes;
+            }
+            if editor || no_field_flags {
+                match open_editor(&snippet.code) {
+                    Ok(code) => snippet.code = code,
+                    Err(e) => {
+                        eprintln!("Editor failed: {}", e);
+                        std::process::exit(1);
                     }
                 }
             }
-            Err(e) => eprintln!("Error loading snippets: {}", e),
-        },
-        Commands::Remove { description } => match load_snippets() {
-            Ok(snippets) => {
-                let snippet_opt = snippets.iter().find(|s| s.description == description);
-                if let Some(snippet) = snippet_opt {
-                    if let Err(e) = snippets::delete_snippet(&snippet.id) {
-                        eprintln!("Error deleting snippet: {}", e);
+
+            if serde_json::to_value(&snippet).ok() == original {
+                println!("No changes to '{}'.", snippet.description);
+                return;
+            }
+            snippet.touch();
+            match store.put(&snippet) {
+                Ok(()) => println!("Snippet '{}' updated.", snippet.description),
+                Err(e) => {
+                    eprintln!("Error saving snippet: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Fork { target } => {
+            let mut store = open_store_or_exit();
+            let mut snippet = select_or_exit(store.as_ref(), &target);
+            if snippet.origin != Origin::Shared {
+                eprintln!("'{}' is not a shared snippet.", snippet.description);
+                std::process::exit(1);
+            }
+            snippet.origin = Origin::User;
+            snippet.touch();
+            match store.put(&snippet) {
+                Ok(()) => println!(
+                    "Forked '{}' into your library; your copy now overrides the shared one.",
+                    snippet.description
+                ),
+                Err(e) => {
+                    eprintln!("Error saving snippet: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Get {
+            target,
+            copy,
+            vars,
+            raw,
+        } => {
+            let snippet = select_or_exit(open_store_or_exit().as_ref(), &target);
+            let code = if raw {
+                snippet.code
+            } else {
+                match template::render(&snippet.code, &vars.into_iter().collect()) {
+                    Ok(code) => code,
+                    Err(e) => {
+                        eprintln!("Cannot fill '{}': {}", snippet.description, e);
+                        std::process::exit(1);
+                    }
+                }
+            };
+            if copy {
+                if let Err(e) = clipboard::copy(&code) {
+                    eprintln!("Failed to copy to clipboard: {}", e);
+                    std::process::exit(1);
+                }
+                eprintln!("✅ Snippet '{}' copied to clipboard!", snippet.description);
+            } else {
+                print!("{}", code);
+            }
+        }
+        Commands::Run { target, vars, yes } => {
+            let snippet = select_or_exit(open_store_or_exit().as_ref(), &target);
+            let mut values: HashMap<String, String> = vars.into_iter().collect();
+            if !yes && io::stdin().is_terminal() {
+                prompt_placeholders(&snippet.code, &mut values);
+            }
+            let command = match template::render(&snippet.code, &values) {
+                Ok(command) => command,
+                Err(e) => {
+                    eprintln!("Cannot fill '{}': {}", snippet.description, e);
+                    std::process::exit(1);
+                }
+            };
+
+            if !yes {
+                eprintln!("About to run via {}:\n", runner::shell_name());
+                for line in command.lines() {
+                    eprintln!("  {}", line);
+                }
+                if !confirm("\nContinue? [y/N] ") {
+                    eprintln!("Aborted.");
+                    std::process::exit(1);
+                }
+            }
+            match runner::run(&command) {
+                Ok(status) => {
+                    eprintln!("Command {}.", runner::describe(status));
+                    std::process::exit(status.code().unwrap_or(1));
+                }
+                Err(e) => {
+                    eprintln!("Failed to start {}: {}", runner::shell_name(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::History { target } => {
+            let store = open_store_or_exit();
+            let snippet = select_or_exit(store.as_ref(), &target);
+            let revisions = history_or_exit(store.as_ref(), &snippet);
+            println!("History of '{}' ({}):", snippet.description, snippet.id);
+            println!("  current  {}", revision_line(&snippet));
+            for revision in revisions.iter().rev() {
+                println!(
+                    "  {:<7}  {}",
+                    revision.number,
+                    revision_line(&revision.snippet)
+                );
+            }
+        }
+        Commands::Diff { target, rev } => {
+            let store = open_store_or_exit();
+            let snippet = select_or_exit(store.as_ref(), &target);
+            let old = revision_or_exit(store.as_ref(), &snippet, rev).snippet;
+            println!("--- revision {} ({})", rev, revision_time(&old));
+            println!("+++ current ({})", revision_time(&snippet));
+            if old.description != snippet.description {
+                println!(
+                    "description: {} -> {}",
+                    old.description, snippet.description
+                );
+            }
+            if old.tags != snippet.tags {
+                println!("tags: {:?} -> {:?}", old.tags, snippet.tags);
+            }
+            for (field, before, after) in [
+                ("language", &old.language, &snippet.language),
+                ("source", &old.source, &snippet.source),
+                ("notes", &old.notes, &snippet.notes),
+            ] {
+                if before != after {
+                    println!(
+                        "{}: {} -> {}",
+                        field,
+                        before.as_deref().unwrap_or("(none)"),
+                        after.as_deref().unwrap_or("(none)")
+                    );
+                }
+            }
+            for line in diff::lines(&old.code, &snippet.code) {
+                println!("{}", line.render());
+            }
+        }
+        Commands::Restore { target, rev } => {
+            let mut store = open_store_or_exit();
+            let snippet = select_or_exit(store.as_ref(), &target);
+            let mut restored = revision_or_exit(store.as_ref(), &snippet, rev).snippet;
+            restored.origin = snippet.origin;
+            restored.touch();
+            match store.put(&restored) {
+                Ok(()) => println!("Restored '{}' to revision {}.", restored.description, rev),
+                Err(e) => {
+                    eprintln!("Error saving snippet: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::List { query, sort } => {
+            let sort = sort.unwrap_or(config::get().default_sort);
+            let store = open_store_or_exit();
+            let result = match query {
+                Some(q) => store.query(&q),
+                None => store.list(),
+            };
+            match result {
+                Ok(mut snippets) => {
+                    if snippets.is_empty() {
+                        println!("No snippets found.");
                     } else {
-                        println!("Snippet '{}' deleted successfully.", description);
+                        println!("Found {} snippets:", snippets.len());
+                        snippets.sort_by(|a, b| sort.compare(a, b));
+                        for snippet in &snippets {
+                            print_snippet(snippet);
+                        }
                     }
-                } else {
-                    println!("No snippet found with description '{}'.", description);
                 }
+                Err(e) => eprintln!("Error loading snippets: {}", e),
             }
-            Err(e) => eprintln!("Error loading snippets: {}", e),
-        },
-        Commands::Interactive => {
-            let all_snippets = match load_snippets() {
+        }
+        Commands::Search {
+            query,
+            limit,
+            tag,
+            json,
+            tsv,
+        } => {
+            let store = open_store_or_exit();
+            let snippets = match search::candidates(store.as_ref(), &query) {
                 Ok(snippets) => snippets,
                 Err(e) => {
-                    eprintln!("Failed to load snippets: {}", e);
-                    return;
+                    eprintln!("Error searching snippets: {}", e);
+                    std::process::exit(1);
                 }
             };
+            let mut hits = search::rank(snippets, &query);
+            hits.retain(|hit| search::has_tags(&hit.snippet, &tag));
+            hits.truncate(limit.unwrap_or(usize::MAX));
 
-            match tui::run_tui(all_snippets) {
-                Ok(Some(_)) => {
-                    println!("✅ Snippet copied to clipboard!");
+            if json {
+                print_hits_json(&hits);
+            } else if tsv {
+                print_hits_tsv(&hits);
+            } else if hits.is_empty() {
+                println!("No snippets match '{}'.", query);
+            } else {
+                for hit in &hits {
+                    println!(
+                        "- {} (Tags: {:?}) [score {}]",
+                        hit.snippet.description, hit.snippet.tags, hit.score
+                    );
+                }
+            }
+        }
+        Commands::Remove { description } => {
+            let mut store = open_store_or_exit();
+            match store.list() {
+                Ok(snippets) => {
+                    let snippet_opt = snippets.iter().find(|s| s.description == description);
+                    if let Some(snippet) = snippet_opt {
+                        if let Err(e) = store.delete(&snippet.id) {
+                            eprintln!("Error deleting snippet: {}", e);
+                        } else {
+                            println!("Snippet '{}' deleted successfully.", description);
+                        }
+                    } else {
+                        println!("No snippet found with description '{}'.", description);
+                    }
                 }
+                Err(e) => eprintln!("Error loading snippets: {}", e),
+            }
+        }
+        Commands::Migrate { dry_run } => match open_store_or_exit().migrate(dry_run) {
+            Ok(upgrades) => {
+                let verb = if dry_run { "Would upgrade" } else { "Upgraded" };
+                for upgrade in &upgrades {
+                    println!(
+                        "{} {}: v{} -> v{}",
+                        verb,
+                        upgrade.item,
+                        upgrade.from,
+                        migrate::CURRENT_SCHEMA_VERSION
+                    );
+                }
+                if upgrades.is_empty() {
+                    println!(
+                        "All snippets are at schema version {}.",
+                        migrate::CURRENT_SCHEMA_VERSION
+                    );
+                } else {
+                    println!("{} {} snippets.", verb, upgrades.len());
+                }
+            }
+            Err(e) => {
+                eprintln!("Migration failed: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Interactive { output, print } => {
+            let output = if print { tui::Output::Stdout } else { output };
+            match tui::run_tui(open_store_or_exit(), library::active(), output) {
+                Ok(Some(code)) if output == tui::Output::Stdout => print!("{}", code),
+                Ok(Some(code)) => match clipboard::copy(&code) {
+                    Ok(()) => println!("✅ Snippet copied to clipboard!"),
+                    Err(e) => {
+                        eprintln!("Failed to copy to clipboard: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+                Ok(None) if output == tui::Output::Stdout => std::process::exit(1),
                 Ok(None) => {
                     println!("No snippet selected.");
                 }
@@ -165,11 +706,250 @@ fn main() {
                 }
             }
         }
+        Commands::Sync { remote, prefer } => {
+            let git = &config::get().git;
+            if !git.enabled {
+                eprintln!(
+                    "Git storage is off. Enable it with: snipman config set git.enabled true"
+                );
+                std::process::exit(1);
+            }
+            let remote = remote.or_else(|| git.remote.clone());
+            let mut resolve = |conflict: &store::git::Conflict| match prefer {
+                Some(resolution) => Ok(resolution),
+                None => prompt_resolution(conflict),
+            };
+            match store::git::sync(
+                &snippets::get_snippets_dir(),
+                remote.as_deref(),
+                &git.branch,
+                &mut resolve,
+            ) {
+                Ok(report) => println!(
+                    "Synced: pulled {} commits, pushed {}, resolved {} conflicts.",
+                    report.pulled, report.pushed, report.resolved
+                ),
+                Err(e) => {
+                    eprintln!("Sync failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Doctor => {
+            let checks = doctor::run();
+            for check in &checks {
+                println!("{}", check);
+            }
+            let count = |status| checks.iter().filter(|c| c.status == status).count();
+            let failed = count(doctor::Status::Fail);
+            println!(
+                "\n{} passed, {} warnings, {} failed",
+                count(doctor::Status::Pass),
+                count(doctor::Status::Warn),
+                failed
+            );
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Config { action } => {
+            let result = match action {
+                ConfigAction::Get { key } => config::get_key(&key).map(|value| match value {
+                    Some(value) => println!("{}", display_toml(&value)),
+                    None => {
+                        eprintln!("{} is not set", key);
+                        std::process::exit(1);
+                    }
+                }),
+                ConfigAction::Set { key, value } => {
+                    config::set_key(&key, &value).map(|value| println!("{} = {}", key, value))
+                }
+                ConfigAction::List => config::list().map(|entries| {
+                    for (key, value) in entries {
+                        println!("{} = {}", key, value);
+                    }
+                }),
+                ConfigAction::Path => config::path().map(|path| println!("{}", path.display())),
+            };
+            if let Err(e) = result {
+                eprintln!("Config error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Trash { action } => {
+            let mut store = open_store_or_exit();
+            if let Some(cutoff) = store::trash_cutoff() {
+                let _ = store.empty_trash(Some(cutoff));
+            }
+            let result = store.trash().and_then(|trashed| match action {
+                TrashAction::List => {
+                    if trashed.is_empty() {
+                        println!("The trash is empty.");
+                    }
+                    for t in &trashed {
+                        println!(
+                            "{}  {}  {}{}",
+                            snippets::format_timestamp(t.deleted_at),
+                            t.snippet.id,
+                            t.snippet.origin.marker(),
+                            t.snippet.description
+                        );
+                    }
+                    Ok(())
+                }
+                TrashAction::Restore { target } => {
+                    let found = trashed
+                        .iter()
+                        .find(|t| t.snippet.id == target)
+                        .or_else(|| trashed.iter().find(|t| t.snippet.description == target));
+                    let Some(t) = found else {
+                        eprintln!(
+                            "No deleted snippet with id or description '{}' (see `snipman trash list`).",
+                            target
+                        );
+                        std::process::exit(1);
+                    };
+                    store
+                        .restore_trashed(&t.snippet.id)
+                        .map(|snippet| println!("Restored '{}'.", snippet.description))
+                }
+                TrashAction::Empty { yes } => {
+                    if trashed.is_empty() {
+                        println!("The trash is empty.");
+                        return Ok(());
+                    }
+                    if !yes
+                        && (!io::stdin().is_terminal()
+                            || !confirm(&format!(
+                                "Permanently delete the {} snippets in the trash? [y/N] ",
+                                trashed.len()
+                            )))
+                    {
+                        eprintln!("Aborted.");
+                        std::process::exit(1);
+                    }
+                    store
+                        .empty_trash(None)
+                        .map(|n| println!("Permanently deleted {} snippets.", n))
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Trash error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Library { action } => {
+            let result = match action {
+                LibraryAction::List => library::list().map(|names| {
+                    for name in names {
+                        let marker = if name == library::active() { "*" } else { " " };
+                        match store::open_library(&name).and_then(|store| store.list()) {
+                            Ok(snippets) => {
+                                println!("{} {} ({} snippets)", marker, name, snippets.len())
+                            }
+                            Err(e) => println!("{} {} (unreadable: {})", marker, name, e),
+                        }
+                    }
+                }),
+                LibraryAction::Create { name } => library::create(&name).map(|dir| {
+                    println!("Created library '{}' in {}.", name, dir.display());
+                    println!("Use it with: snipman --library {} ...", name);
+                }),
+                LibraryAction::Remove { name, yes } => {
+                    if !yes && library::exists(&name) {
+                        let count = store::open_library(&name)
+                            .and_then(|store| store.list())
+                            .map_or(0, |snippets| snippets.len());
+                        if !io::stdin().is_terminal()
+                            || !confirm(&format!(
+                                "Delete library '{}' and its {} snippets? [y/N] ",
+                                name, count
+                            ))
+                        {
+                            eprintln!("Aborted.");
+                            std::process::exit(1);
+                        }
+                    }
+                    library::remove(&name).map(|()| println!("Removed library '{}'.", name))
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Library error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Uninstall {
+            dry_run,
+            purge,
+            yes,
+        } => {
+            let plan = match init::uninstall_plan(purge) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    eprintln!("Uninstall failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if plan.is_empty() {
+                println!("Nothing to remove.");
+                return;
+            }
+            if dry_run {
+                println!("Would remove:");
+                for item in &plan {
+                    println!("  {}", item);
+                }
+                return;
+            }
+            if purge && !yes {
+                for item in plan.iter().filter(|i| matches!(i, init::Removal::Dir(_))) {
+                    eprintln!("  {}", item);
+                }
+                if !io::stdin().is_terminal()
+                    || !confirm("This permanently deletes ALL snippets and settings above. Continue? [y/N] ")
+                {
+                    eprintln!("Aborted.");
+                    std::process::exit(1);
+                }
+            }
+            let failed = init::uninstall(&plan);
+            if failed > 0 {
+                eprintln!("{} item(s) could not be removed.", failed);
+                std::process::exit(1);
+            }
+            println!("Uninstall completed.");
+        }
         Commands::Install {
             shell,
             no_modify_rc,
+            keybindings,
+            remove_keybindings,
+            check,
+            force,
         } => {
-            if let Err(e) = init::install_user_assets(shell, no_modify_rc) {
+            if check {
+                match init::check_install() {
+                    Ok(drift) if drift.is_empty() => println!("Installation matches the manifest."),
+                    Ok(drift) => {
+                        for item in &drift {
+                            println!("{}", item);
+                        }
+                        eprintln!("Run `snipman install` to repair (add --force to overwrite modified items).");
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("Check failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            let options = init::InstallOptions {
+                shell,
+                no_modify_rc,
+                keybindings: keybindings || (!remove_keybindings && init::keybindings_installed()),
+            };
+            if let Err(e) = init::install_user_assets(&options, force) {
                 eprintln!("Install failed: {}", e);
                 std::process::exit(1);
             } else {
@@ -179,10 +959,311 @@ fn main() {
     }
 }
 
+/// Subcommands of `config`.
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective value of a key, e.g. `tui.keys.quit`
+    Get { key: String },
+    /// Set a key in the config file
+    Set { key: String, value: String },
+    /// Print every setting with its effective value
+    List,
+    /// Print the location of the config file
+    Path,
+}
+
+/// Subcommands of `library`.
+#[derive(Subcommand)]
+enum LibraryAction {
+    /// List libraries with their snippet counts; `*` marks the one in use
+    List,
+    /// Create an empty library
+    Create { name: String },
+    /// Delete a library and all of its snippets
+    Remove {
+        name: String,
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// Subcommands of `trash`.
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List deleted snippets, most recently deleted first
+    List,
+    /// Put a deleted snippet back
+    Restore {
+        /// Id or exact description of the deleted snippet
+        target: String,
+    },
+    /// Permanently delete everything in the trash
+    Empty {
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
 fn requires_install_gate(cmd: &Commands) -> bool {
-    match cmd {
-        Commands::Install { .. } => false,
-        _ => true,
+    !matches!(
+        cmd,
+        Commands::Install { .. }
+            | Commands::Uninstall { .. }
+            | Commands::Doctor
+            | Commands::Config { .. }
+    )
+}
+
+/// After a version upgrade, regenerate installed assets, or tell the user to when
+/// auto-upgrade is disabled. Never fails the command being run.
+fn refresh_stale_install(no_auto_upgrade: bool) {
+    let disabled = no_auto_upgrade
+        || std::env::var("SNIPMAN_NO_AUTO_UPGRADE").is_ok_and(|v| !v.is_empty() && v != "0");
+    if disabled {
+        if let Some(old) = init::stale_install_version() {
+            eprintln!(
+                "note: installed assets are from snipman {} (now {}); run `snipman install` to refresh them.",
+                old,
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+        return;
+    }
+    match init::upgrade_if_needed() {
+        Ok(Some(old)) => eprintln!(
+            "snipman upgraded from {} to {}: refreshed man page and completions.",
+            old,
+            env!("CARGO_PKG_VERSION")
+        ),
+        Ok(None) => {}
+        Err(e) => eprintln!("warning: could not refresh installed assets: {}", e),
+    }
+}
+
+/// Print a snippet's description and tags, followed by its metadata indented.
+fn print_snippet(snippet: &Snippet) {
+    println!(
+        "- {}{} (Tags: {:?})",
+        snippet.origin.marker(),
+        snippet.description,
+        snippet.tags
+    );
+    for line in snippet.metadata_lines() {
+        println!("    {}", line);
+    }
+}
+
+/// Ask on the terminal for every placeholder in `code` that has no value yet.
+/// An empty answer keeps the placeholder's default.
+fn prompt_placeholders(code: &str, values: &mut HashMap<String, String>) {
+    for placeholder in template::placeholders(code) {
+        if values.contains_key(&placeholder.name) {
+            continue;
+        }
+        match &placeholder.default {
+            Some(default) => eprint!("{} [{}]: ", placeholder.label(), default),
+            None => eprint!("{}: ", placeholder.label()),
+        }
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() {
+            return;
+        }
+        let answer = answer.trim_end_matches(['\r', '\n']);
+        if !answer.is_empty() {
+            values.insert(placeholder.name, answer.to_string());
+        }
+    }
+}
+
+/// Show both versions of a conflicting snippet and ask which to keep.
+fn prompt_resolution(conflict: &store::git::Conflict) -> io::Result<store::git::Resolution> {
+    use store::git::Resolution;
+    if !io::stdin().is_terminal() {
+        return Err(io::Error::other(format!(
+            "snippet {} changed on both sides; run `snipman sync` in a terminal or pass --prefer",
+            conflict.id
+        )));
+    }
+    eprintln!(
+        "\nSnippet {} changed both here and on the remote.",
+        conflict.id
+    );
+    for (side, version) in [("mine", &conflict.mine), ("theirs", &conflict.theirs)] {
+        match version {
+            Some(snippet) => {
+                eprintln!(
+                    "{}: {} (Tags: {:?})",
+                    side, snippet.description, snippet.tags
+                );
+                for line in snippet.metadata_lines() {
+                    eprintln!("  {}", line);
+                }
+                for line in snippet.code.lines() {
+                    eprintln!("    {}", line);
+                }
+            }
+            None => eprintln!("{}: deleted", side),
+        }
+    }
+    loop {
+        eprint!("Keep [m]ine, [t]heirs or [b]oth? ");
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "no answer; merge aborted",
+            ));
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "m" | "mine" => return Ok(Resolution::Mine),
+            "t" | "theirs" => return Ok(Resolution::Theirs),
+            "b" | "both" => return Ok(Resolution::Both),
+            _ => {}
+        }
+    }
+}
+
+/// Print `prompt` to stderr and return true if the user answers y/yes.
+fn confirm(prompt: &str) -> bool {
+    eprint!("{}", prompt);
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// A config value for `config get`: strings without quotes, everything else as TOML.
+fn display_toml(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Print search hits as a JSON array of snippets, each with an added `score`.
+fn print_hits_json(hits: &[search::Hit]) {
+    #[derive(serde::Serialize)]
+    struct JsonHit<'a> {
+        score: i64,
+        #[serde(flatten)]
+        snippet: &'a Snippet,
+    }
+
+    let out: Vec<JsonHit> = hits
+        .iter()
+        .map(|hit| JsonHit {
+            score: hit.score,
+            snippet: &hit.snippet,
+        })
+        .collect();
+    match serde_json::to_string_pretty(&out) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Error serializing results: {}", e),
+    }
+}
+
+/// Print search hits as tab-separated lines. Tabs and newlines inside fields
+/// are replaced by spaces so every hit stays on one line.
+fn print_hits_tsv(hits: &[search::Hit]) {
+    let clean = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+    for hit in hits {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            hit.score,
+            hit.snippet.id,
+            clean(&hit.snippet.description),
+            clean(&hit.snippet.tags.join(",")),
+            clean(hit.snippet.language.as_deref().unwrap_or(""))
+        );
+    }
+}
+
+/// Earlier versions of `snippet`, exiting with status 1 if there are none.
+fn history_or_exit(store: &dyn SnippetStore, snippet: &Snippet) -> Vec<store::Revision> {
+    match store.history(&snippet.id) {
+        Ok(revisions) if revisions.is_empty() => {
+            eprintln!("'{}' has no earlier versions.", snippet.description);
+            std::process::exit(1);
+        }
+        Ok(revisions) => revisions,
+        Err(e) => {
+            eprintln!("Error loading history: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Revision `rev` of `snippet`, exiting with status 1 if it does not exist.
+fn revision_or_exit(store: &dyn SnippetStore, snippet: &Snippet, rev: u32) -> store::Revision {
+    let revisions = history_or_exit(store, snippet);
+    let newest = revisions.last().map_or(0, |r| r.number);
+    match revisions.into_iter().find(|r| r.number == rev) {
+        Some(revision) => revision,
+        None => {
+            eprintln!(
+                "'{}' has no revision {} (revisions 1 to {}, see `snipman history`).",
+                snippet.description, rev, newest
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// When a version of a snippet was written.
+fn revision_time(snippet: &Snippet) -> String {
+    snippet
+        .updated_at
+        .or(snippet.created_at)
+        .map_or_else(|| "unknown time".to_string(), snippets::format_timestamp)
+}
+
+/// `time  description  (first line of code)` for `history`.
+fn revision_line(snippet: &Snippet) -> String {
+    format!(
+        "{}  {}  ({})",
+        revision_time(snippet),
+        snippet.description,
+        snippet.code.lines().next().unwrap_or("")
+    )
+}
+
+/// Resolve `target` to a single snippet, exiting with status 1 if nothing or
+/// more than one snippet matches.
+fn select_or_exit(store: &dyn SnippetStore, target: &str) -> Snippet {
+    match select::select(store, target) {
+        Ok(Selection::Found(snippet)) => snippet,
+        Ok(Selection::NotFound) => {
+            eprintln!("No snippet matches '{}'.", target);
+            std::process::exit(1);
+        }
+        Ok(Selection::Ambiguous(candidates)) => {
+            eprintln!(
+                "'{}' matches several snippets; use one of these ids:",
+                target
+            );
+            for snippet in candidates {
+                eprintln!("  {}  {}", snippet.id, snippet.description);
+            }
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error loading snippets: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Open the configured snippet store, exiting with an error message on failure.
+fn open_store_or_exit() -> Box<dyn SnippetStore> {
+    match store::open_store() {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Failed to open snippet store: {}", e);
+            std::process::exit(1);
+        }
     }
 }
 
@@ -214,135 +1295,10 @@ fn resolve_code_input(
         return Ok(buf);
     }
     if editor {
-        return open_editor();
+        return open_editor("");
     }
     Err(io::Error::new(
         io::ErrorKind::InvalidInput,
         "no code source provided",
     ))
 }
-
-/// Open a text editor to compose a snippet body and return its contents.
-///
-/// Editor resolution order:
-/// - $VISUAL, then $EDITOR if set (parsed with a minimal shell-like splitter)
-/// - Windows: notepad.exe
-/// - macOS: `open -W -t`
-/// - Other Unix: prefers `nano` if available, otherwise `vi`
-///
-/// Returns the edited text, or an error if the editor fails to launch or exits non-zero.
-fn open_editor() -> io::Result<String> {
-    let mut path = env::temp_dir();
-    path.push(format!("snipman_{}.txt", std::process::id()));
-    fs::write(&path, "")?;
-
-    // Prefer $VISUAL, then $EDITOR
-    let editor_spec = env::var("VISUAL").or_else(|_| env::var("EDITOR")).ok();
-    let mut cmd;
-
-    if let Some(spec) = editor_spec {
-        let mut parts = parse_cmdline(&spec);
-        if parts.is_empty() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "empty $VISUAL/$EDITOR",
-            ));
-        }
-        let prog = parts.remove(0);
-        cmd = Command::new(prog);
-        cmd.args(parts).arg(&path);
-    } else if OsKind::current() == OsKind::Windows {
-        cmd = Command::new("notepad.exe");
-        cmd.arg(&path);
-    } else if OsKind::current() == OsKind::Macos {
-        cmd = Command::new("open");
-        cmd.args(["-W", "-t"]).arg(&path);
-    } else {
-        let prefer_nano = Command::new("nano").arg("--version").status().is_ok();
-        cmd = if prefer_nano {
-            Command::new("nano")
-        } else {
-            Command::new("vi")
-        };
-        cmd.arg(&path);
-    }
-
-    let status = cmd.status()?;
-    if !status.success() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "editor exited with non-zero status",
-        ));
-    }
-
-    let contents = fs::read_to_string(&path)?;
-    let _ = fs::remove_file(&path);
-    Ok(contents)
-}
-
-/// Minimal shell‑like splitter for `$VISUAL`/`$EDITOR`.
-///
-/// Splits a command string into argv without invoking a shell.
-///
-/// Behavior:
-/// - Whitespace outside quotes separates arguments.
-/// - Single quotes `'...'` take text literally; backslashes have no special meaning inside.
-/// - Double quotes `"..."` group text; backslash `\` escapes the next character inside.
-/// - Outside single quotes, a backslash `\` escapes the next character (including space and quotes).
-/// - Quote characters are not included in results unless escaped inside double quotes.
-/// - Unclosed quotes are tolerated: remaining text goes into the current token.
-/// - A trailing standalone backslash is ignored.
-///
-/// Not a full shell parser:
-/// - No variable expansion, globbing, pipelines, or command substitution.
-///
-/// # Examples:
-/// ```rust,ignore
-/// assert_eq!(parse_cmdline(r#"code -w"#), ["code", "-w"]);
-/// assert_eq!(parse_cmdline(r#"my\ editor --flag"#), ["my editor", "--flag"]);
-/// assert_eq!(parse_cmdline(r#"nvim "+set ft=rust""#), ["nvim", "+set ft=rust"]);
-/// assert_eq!(
-///     parse_cmdline(r#"sh -c "echo \"hi\" 'and bye'""#),
-///     ["sh", "-c", r#"echo "hi" 'and bye'"#]
-/// );
-/// assert_eq!(
-///     parse_cmdline(r#"--ext=\*.rs 'path with space'/file"#),
-///     ["--ext=*.rs", "path with space/file"]
-/// );
-/// ```
-fn parse_cmdline(s: &str) -> Vec<String> {
-    let mut args = Vec::new();
-    let mut buf = String::new();
-    let mut in_single = false;
-    let mut in_double = false;
-    let mut escape = false;
-
-    for ch in s.chars() {
-        if escape {
-            buf.push(ch);
-            escape = false;
-            continue;
-        }
-        match ch {
-            '\\' if !in_single => {
-                escape = true;
-            }
-            '\'' if !in_double => {
-                in_single = !in_single;
-            }
-            '"' if !in_single => {
-                in_double = !in_double;
-            }
-            c if c.is_whitespace() && !in_single && !in_double => {
-                if !buf.is_empty() {
-                    args.push(std::mem::take(&mut buf));
-                }
-            }
-            _ => buf.push(ch),
-        }
-    }
-    if !buf.is_empty() {
-        args.push(buf);
-    }
-    args
-}
@@ -0,0 +1,95 @@
+//! On-disk schema versioning for snippet documents.
+//!
+//! Every stored snippet carries a `schema_version`. Documents written before
+//! versioning existed have no such field and count as version 0. On load, older
+//! documents are upgraded one step at a time by [`MIGRATIONS`] before being
+//! deserialized into [`crate::snippets::Snippet`], so a format change never makes
+//! existing snippets unreadable.
+
+use serde_json::{Map, Value};
+use std::io;
+
+/// Schema version written by this build.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a document from version `n` to `n + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); CURRENT_SCHEMA_VERSION as usize] = [v0_to_v1];
+
+/// v1 makes metadata explicit: tags and the optional metadata fields are
+/// always present (possibly `null`).
+fn v0_to_v1(doc: &mut Map<String, Value>) {
+    doc.entry("tags")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    for key in ["created_at", "updated_at", "language", "source", "notes"] {
+        doc.entry(key).or_insert(Value::Null);
+    }
+}
+
+/// A document that was (or would be) upgraded.
+pub struct Upgrade {
+    /// File path or id identifying the document.
+    pub item: String,
+    /// Version the document was at before upgrading.
+    pub from: u32,
+}
+
+/// Schema version recorded in `value`, treating a missing field as version 0.
+pub fn version_of(value: &Value) -> u32 {
+    value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .map_or(0, |v| v as u32)
+}
+
+/// Upgrade a raw snippet document to [`CURRENT_SCHEMA_VERSION`] in place.
+///
+/// Returns the original version if anything changed, `None` if the document was
+/// already current.
+///
+/// # Errors
+/// Returns `InvalidData` if the document is not a JSON object or was written by
+/// a newer snipman.
+pub fn upgrade(value: &mut Value) -> io::Result<Option<u32>> {
+    let from = version_of(value);
+    if from > CURRENT_SCHEMA_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "schema version {} is newer than supported version {}; upgrade snipman",
+                from, CURRENT_SCHEMA_VERSION
+            ),
+        ));
+    }
+    if from == CURRENT_SCHEMA_VERSION {
+        return Ok(None);
+    }
+    let doc = value.as_object_mut().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "snippet is not a JSON object")
+    })?;
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(doc);
+    }
+    doc.insert("schema_version".into(), CURRENT_SCHEMA_VERSION.into());
+    Ok(Some(from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrades_unversioned_documents() {
+        let mut value: Value =
+            serde_json::from_str(r#"{"id":"1","description":"d","code":"c"}"#).unwrap();
+        assert_eq!(upgrade(&mut value).unwrap(), Some(0));
+        assert_eq!(version_of(&value), CURRENT_SCHEMA_VERSION);
+        assert_eq!(value["tags"], Value::Array(vec![]));
+        assert_eq!(upgrade(&mut value).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_newer_documents() {
+        let mut value: Value = serde_json::from_str(r#"{"schema_version":99}"#).unwrap();
+        assert!(upgrade(&mut value).is_err());
+    }
+}
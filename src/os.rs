@@ -30,6 +30,14 @@ impl OsKind {
         matches!(self, OsKind::Linux | OsKind::Macos)
     }
 
+    /// Path of the controlling terminal device (`/dev/tty`, or `CONOUT$` on Windows).
+    pub fn tty_path(&self) -> &'static str {
+        match self {
+            OsKind::Windows => "CONOUT$",
+            _ => "/dev/tty",
+        }
+    }
+
     /// Returns a short, human-readable name for the OS.
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -0,0 +1,71 @@
+//! Project-local snippets shipped in a repository's `.snipman/` directory.
+//!
+//! The directory is found by walking up from the current working directory and
+//! holds snippet files like a library (`.snipman/snippets/<id>.json`). Its
+//! snippets are layered over the user's library by
+//! [`crate::store::layered::LayeredStore`].
+//!
+//! The directory is usually checked into the project's repository, so snipman
+//! keeps everything else about it (history, trash, backups, the lock file) in
+//! [`state_dir`] under the user's data root.
+
+use crate::fsutil::sha256_hex;
+use crate::snippets::{default_data_root, get_data_root};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Name of the project directory looked for in the working directory and its parents.
+pub const DIR_NAME: &str = ".snipman";
+
+/// The nearest `.snipman` directory in `start` or one of its parents.
+///
+/// The user's own data root is skipped, in case it is itself named `.snipman`
+/// and lies above `start`.
+pub fn find(start: &Path) -> Option<PathBuf> {
+    let data_roots: Vec<PathBuf> = [default_data_root(), get_data_root()]
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .collect();
+    start
+        .ancestors()
+        .map(|dir| dir.join(DIR_NAME))
+        .filter(|candidate| candidate.is_dir())
+        .find(|candidate| {
+            candidate
+                .canonicalize()
+                .is_ok_and(|c| !data_roots.contains(&c))
+        })
+}
+
+/// The `.snipman` directory of the project containing the working directory, if any.
+pub fn current() -> Option<PathBuf> {
+    find(&env::current_dir().ok()?)
+}
+
+/// Directory outside the repository for snipman's own files about the project
+/// at `project_dir`: `projects/<hash of its path>/` in the data root.
+pub fn state_dir(project_dir: &Path) -> PathBuf {
+    let key = project_dir
+        .canonicalize()
+        .unwrap_or_else(|_| project_dir.to_path_buf());
+    let hash = sha256_hex(key.to_string_lossy().as_bytes());
+    get_data_root().join("projects").join(&hash[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsutil::TempDir;
+    use std::fs;
+
+    #[test]
+    fn finds_the_nearest_project_directory() {
+        let root = TempDir::new();
+        let nested = root.join("repo/src/deep");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find(&nested), None);
+
+        fs::create_dir_all(root.join("repo").join(DIR_NAME)).unwrap();
+        assert_eq!(find(&nested), Some(root.join("repo").join(DIR_NAME)));
+    }
+}
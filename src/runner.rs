@@ -0,0 +1,40 @@
+//! Execute snippet bodies as shell commands.
+
+use crate::os::OsKind;
+use crate::shell::ShellTarget;
+use std::io;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Name of the shell used to run commands: the user's shell detected from
+/// `$SHELL` (bash, zsh or fish), otherwise `sh` on Unix and `cmd` on Windows.
+pub fn shell_name() -> &'static str {
+    match ShellTarget::detect().and_then(|t| t.program()) {
+        Some(program) => program,
+        None if OsKind::current() == OsKind::Windows => "cmd",
+        None => "sh",
+    }
+}
+
+/// Run `command` through [`shell_name`], inheriting stdin/stdout/stderr, and
+/// wait for it to finish.
+pub fn run(command: &str) -> io::Result<ExitStatus> {
+    run_with(command, Stdio::inherit())
+}
+
+/// Like [`run`], but with the command's stdout connected to `stdout`.
+pub fn run_with(command: &str, stdout: Stdio) -> io::Result<ExitStatus> {
+    let shell = shell_name();
+    let flag = if shell == "cmd" { "/C" } else { "-c" };
+    Command::new(shell)
+        .args([flag, command])
+        .stdout(stdout)
+        .status()
+}
+
+/// Short human-readable description of how a command finished.
+pub fn describe(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("exited with status {}", code),
+        None => "terminated by a signal".to_string(),
+    }
+}
@@ -0,0 +1,87 @@
+//! Fuzzy scoring of snippets against a query.
+//!
+//! Shared by the TUI filter, the `search` command and target selection so every
+//! entry point ranks snippets the same way.
+
+use crate::config;
+use crate::snippets::Snippet;
+use crate::store::SnippetStore;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::io;
+
+/// A snippet that matched a query, with its score (higher is better).
+pub struct Hit {
+    pub snippet: Snippet,
+    pub score: i64,
+}
+
+/// Best fuzzy score of `query` against the snippet's description, tags and code,
+/// each multiplied by its `search.weights` entry from the config, or `None` if
+/// none of them match.
+pub fn score(matcher: &SkimMatcherV2, snippet: &Snippet, query: &str) -> Option<i64> {
+    let weights = &config::get().search.weights;
+    [
+        (&snippet.description, weights.description),
+        (&snippet.tags.join(" "), weights.tags),
+        (&snippet.code, weights.code),
+    ]
+    .into_iter()
+    .filter_map(|(field, weight)| matcher.fuzzy_match(field, query).map(|s| s * weight))
+    .max()
+}
+
+/// Score every snippet against `query`, dropping non-matches, best first.
+pub fn rank(snippets: Vec<Snippet>, query: &str) -> Vec<Hit> {
+    let matcher = SkimMatcherV2::default();
+    let mut hits: Vec<Hit> = snippets
+        .into_iter()
+        .filter_map(|snippet| score(&matcher, &snippet, query).map(|score| Hit { snippet, score }))
+        .collect();
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+    hits
+}
+
+/// Snippets worth ranking against `query`.
+///
+/// The backend's index narrows the candidates when it has one, but it only
+/// matches whole words and prefixes, so a query it finds nothing for (such as
+/// the typo `dockr`) falls back to every snippet.
+pub fn candidates(store: &dyn SnippetStore, query: &str) -> io::Result<Vec<Snippet>> {
+    if store.has_index() {
+        let hits = store.query(query)?;
+        if !hits.is_empty() {
+            return Ok(hits);
+        }
+    }
+    store.list()
+}
+
+/// Whether the snippet carries every tag in `tags` (case-insensitive).
+pub fn has_tags(snippet: &Snippet, tags: &[String]) -> bool {
+    tags.iter()
+        .all(|want| snippet.tags.iter().any(|t| t.eq_ignore_ascii_case(want)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_best_match_first() {
+        let snippets = vec![
+            Snippet::new("list files".into(), vec!["fs".into()], "ls".into()),
+            Snippet::new(
+                "docker logs".into(),
+                vec!["docker".into()],
+                "docker logs -f".into(),
+            ),
+            Snippet::new("unrelated".into(), vec![], "true".into()),
+        ];
+        let hits = rank(snippets, "dockr");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet.description, "docker logs");
+        assert!(has_tags(&hits[0].snippet, &["DOCKER".to_string()]));
+        assert!(!has_tags(&hits[0].snippet, &["fs".to_string()]));
+    }
+}
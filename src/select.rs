@@ -0,0 +1,156 @@
+//! Resolve a user-supplied target (id, description or fuzzy query) to one snippet.
+//!
+//! Used by commands that act on a single snippet, such as `edit`.
+
+use crate::search;
+use crate::snippets::Snippet;
+use crate::store::SnippetStore;
+use std::io;
+
+/// Outcome of resolving a target.
+pub enum Selection {
+    /// Exactly one snippet matched.
+    Found(Snippet),
+    /// Nothing matched.
+    NotFound,
+    /// Several snippets matched equally well.
+    Ambiguous(Vec<Snippet>),
+}
+
+/// Resolve `target` against the store.
+///
+/// Precedence:
+/// 1. A snippet whose id equals `target`.
+/// 2. Snippets whose description equals `target` (case-insensitive); more than one is ambiguous.
+/// 3. The best fuzzy match over description, tags and code; a tie for the best score is ambiguous.
+pub fn select(store: &dyn SnippetStore, target: &str) -> io::Result<Selection> {
+    if let Ok(Some(snippet)) = store.get(target) {
+        return Ok(Selection::Found(snippet));
+    }
+
+    let snippets = store.list()?;
+    let exact: Vec<Snippet> = snippets
+        .iter()
+        .filter(|s| s.description.eq_ignore_ascii_case(target))
+        .cloned()
+        .collect();
+    if !exact.is_empty() {
+        return Ok(pick(exact));
+    }
+
+    let mut hits = search::rank(snippets, target);
+    let Some(best) = hits.first().map(|hit| hit.score) else {
+        return Ok(Selection::NotFound);
+    };
+    hits.retain(|hit| hit.score == best);
+    Ok(pick(hits.into_iter().map(|hit| hit.snippet).collect()))
+}
+
+fn pick(mut candidates: Vec<Snippet>) -> Selection {
+    match candidates.len() {
+        0 => Selection::NotFound,
+        1 => Selection::Found(candidates.remove(0)),
+        _ => Selection::Ambiguous(candidates),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fsutil::TempDir;
+    use crate::store::JsonDirStore;
+
+    fn store_with(snippets: &[Snippet]) -> (JsonDirStore, TempDir) {
+        let root = TempDir::new();
+        let mut store = JsonDirStore::new(root.join("snippets"));
+        for snippet in snippets {
+            store.put(snippet).unwrap();
+        }
+        (store, root)
+    }
+
+    fn ids(snippets: &[Snippet]) -> Vec<String> {
+        let mut ids: Vec<String> = snippets.iter().map(|s| s.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn an_id_wins_over_descriptions() {
+        let target = Snippet::new(
+            "Restart nginx".into(),
+            vec![],
+            "systemctl restart nginx".into(),
+        );
+        let named_like_id = Snippet::new(target.id.clone(), vec![], "echo".into());
+        let (store, _root) = store_with(&[target.clone(), named_like_id]);
+
+        match select(&store, &target.id).unwrap() {
+            Selection::Found(found) => assert_eq!(found.id, target.id),
+            _ => panic!("expected the snippet with that id"),
+        }
+    }
+
+    #[test]
+    fn an_exact_description_wins_over_fuzzy_matches() {
+        let logs = Snippet::new("Docker logs".into(), vec![], "docker logs -f".into());
+        let longer = Snippet::new("Docker logs of all containers".into(), vec![], "x".into());
+        let (store, _root) = store_with(&[logs.clone(), longer]);
+
+        match select(&store, "docker LOGS").unwrap() {
+            Selection::Found(found) => assert_eq!(found.id, logs.id),
+            _ => panic!("expected the exact description"),
+        }
+    }
+
+    #[test]
+    fn duplicate_descriptions_are_ambiguous() {
+        let a = Snippet::new("Deploy".into(), vec![], "make deploy".into());
+        let b = Snippet::new("deploy".into(), vec![], "./deploy.sh".into());
+        let other = Snippet::new("Deploy docs".into(), vec![], "mkdocs gh-deploy".into());
+        let (store, _root) = store_with(&[a.clone(), b.clone(), other]);
+
+        match select(&store, "Deploy").unwrap() {
+            Selection::Ambiguous(candidates) => {
+                assert_eq!(ids(&candidates), ids(&[a, b]));
+            }
+            _ => panic!("expected both exact descriptions"),
+        }
+    }
+
+    #[test]
+    fn the_best_fuzzy_match_is_found() {
+        let nginx = Snippet::new(
+            "Restart nginx".into(),
+            vec![],
+            "systemctl restart nginx".into(),
+        );
+        let files = Snippet::new("List files".into(), vec![], "ls -la".into());
+        let (store, _root) = store_with(&[nginx.clone(), files]);
+
+        match select(&store, "nginx").unwrap() {
+            Selection::Found(found) => assert_eq!(found.id, nginx.id),
+            _ => panic!("expected the fuzzy match"),
+        }
+        assert!(matches!(
+            select(&store, "kubectl").unwrap(),
+            Selection::NotFound
+        ));
+    }
+
+    #[test]
+    fn a_tie_for_the_best_fuzzy_score_is_ambiguous() {
+        let a = Snippet::new("Tail logs".into(), vec![], "tail -f app.log".into());
+        let b = Snippet::new("Tail logs".into(), vec![], "tail -f app.log".into());
+        let worse = Snippet::new("Total of all lines".into(), vec![], "wc -l".into());
+        let (store, _root) = store_with(&[a.clone(), b.clone(), worse]);
+
+        match select(&store, "tail").unwrap() {
+            Selection::Ambiguous(candidates) => {
+                assert_eq!(ids(&candidates), ids(&[a, b]));
+            }
+            _ => panic!("expected both equally good matches"),
+        }
+    }
+}
@@ -5,6 +5,7 @@
 
 use clap::ValueEnum;
 use clap_complete::Shell;
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::path::Path;
 
@@ -14,8 +15,10 @@ use std::path::Path;
 /// - Auto: detect the current shell from $SHELL (Unix) and use it; if detection fails, fall back to Bash, Zsh and Fish.
 /// - Bash/Zsh/Fish: target only that specific shell.
 /// - All: target Bash, Zsh and Fish.
-#[derive(Clone, ValueEnum, Debug)]
+#[derive(Clone, ValueEnum, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ShellTarget {
+    #[default]
     Auto,
     Bash,
     Zsh,
@@ -44,6 +47,16 @@ impl ShellTarget {
             })
     }
 
+    /// Executable name of a concrete shell, or `None` for `Auto`/`All`.
+    pub fn program(&self) -> Option<&'static str> {
+        match self {
+            ShellTarget::Bash => Some("bash"),
+            ShellTarget::Zsh => Some("zsh"),
+            ShellTarget::Fish => Some("fish"),
+            ShellTarget::Auto | ShellTarget::All => None,
+        }
+    }
+
     /// Convert the high-level target into one or more clap_complete shells.
     ///
     /// For `Auto`, tries detection first; if it fails, includes all shells so
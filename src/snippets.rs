@@ -5,16 +5,31 @@
 //! - Linux:   $XDG_DATA_HOME (or ~/.local/share)/.snipman/snippets
 //! - macOS:   ~/Library/Application Support/.snipman/snippets
 //! - Windows: %APPDATA%/.snipman/snippets
+//!
+//! `SNIPMAN_DATA_DIR` or `data_dir` in the config file move the whole tree.
+//! Named libraries live under `libraries/<name>/snippets` next to the default
+//! one (see [`crate::library`]).
+//!
+//! Reading and writing goes through [`crate::store::SnippetStore`].
 
+use crate::library;
+use crate::migrate::CURRENT_SCHEMA_VERSION;
 use crate::os;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::cmp::Ordering;
 use std::path::PathBuf;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
-/// A single code snippet, with description, tags, and code body.
+/// A single code snippet, with description, tags, code body and metadata.
+///
+/// Metadata fields default to `None` so files written by older versions keep loading.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Snippet {
+    /// On-disk format version, see [`crate::migrate`].
+    #[serde(default)]
+    pub schema_version: u32,
     /// Unique identifier (UUID v4) used as filename on disk.
     pub id: String,
     /// Short, searchable description.
@@ -23,21 +38,182 @@ pub struct Snippet {
     pub tags: Vec<String>,
     /// The snippet body/code.
     pub code: String,
+    /// Unix epoch seconds when the snippet was created.
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    /// Unix epoch seconds of the last change.
+    #[serde(default)]
+    pub updated_at: Option<u64>,
+    /// Language of the body, e.g. "rust" or "bash".
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Where the snippet came from (URL or file path).
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Free-form notes.
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Store the snippet was loaded from; not saved.
+    #[serde(skip)]
+    pub origin: Origin,
+}
+
+/// Which layer of a layered store a snippet belongs to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Origin {
+    /// The user's library.
+    #[default]
+    User,
+    /// The `.snipman/` directory of the current project.
+    Project,
+    /// A read-only shared library from `shared_libraries` in the config file.
+    Shared,
+}
+
+impl Origin {
+    /// Prefix shown before the description in lists; empty for the user library.
+    pub fn marker(self) -> &'static str {
+        match self {
+            Origin::User => "",
+            Origin::Project => "[project] ",
+            Origin::Shared => "🔒 ",
+        }
+    }
 }
 
 impl Snippet {
-    /// Create a new snippet with a random UUID.
+    /// Create a new snippet with a random UUID, stamped with the current time.
     pub fn new(description: String, tags: Vec<String>, code: String) -> Self {
+        let now = now_unix();
         Self {
+            schema_version: CURRENT_SCHEMA_VERSION,
             id: Uuid::new_v4().to_string(),
             description,
             tags,
             code,
+            created_at: Some(now),
+            updated_at: Some(now),
+            language: None,
+            source: None,
+            notes: None,
+            origin: Origin::User,
+        }
+    }
+
+    /// Record that the snippet was just modified.
+    pub fn touch(&mut self) {
+        self.updated_at = Some(now_unix());
+    }
+
+    /// Human-readable `key: value` lines for the metadata that is set.
+    pub fn metadata_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(language) = &self.language {
+            lines.push(format!("language: {}", language));
+        }
+        if let Some(ts) = self.created_at {
+            lines.push(format!("created: {}", format_timestamp(ts)));
+        }
+        if let Some(ts) = self.updated_at
+            && self.updated_at != self.created_at
+        {
+            lines.push(format!("updated: {}", format_timestamp(ts)));
         }
+        if let Some(source) = &self.source {
+            lines.push(format!("source: {}", source));
+        }
+        if let Some(notes) = &self.notes {
+            lines.push(format!("notes: {}", notes));
+        }
+        lines
     }
 }
 
-fn get_snippets_dir() -> PathBuf {
+/// Field used to order snippets in `list` and the TUI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Alphabetically by description.
+    #[default]
+    Description,
+    /// Newest first.
+    Created,
+    /// Most recently changed first.
+    Updated,
+    /// Alphabetically by language; snippets without one go last.
+    Language,
+}
+
+impl SortKey {
+    /// Compare two snippets by this key, falling back to description for ties.
+    pub fn compare(self, a: &Snippet, b: &Snippet) -> Ordering {
+        let by_description = || {
+            a.description
+                .to_lowercase()
+                .cmp(&b.description.to_lowercase())
+        };
+        match self {
+            SortKey::Description => by_description(),
+            SortKey::Created => b.created_at.cmp(&a.created_at).then_with(by_description),
+            SortKey::Updated => b.updated_at.cmp(&a.updated_at).then_with(by_description),
+            SortKey::Language => match (&a.language, &b.language) {
+                (Some(x), Some(y)) => x.to_lowercase().cmp(&y.to_lowercase()),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+            .then_with(by_description),
+        }
+    }
+
+    /// The next key in display order, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Description => SortKey::Created,
+            SortKey::Created => SortKey::Updated,
+            SortKey::Updated => SortKey::Language,
+            SortKey::Language => SortKey::Description,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Description => "description",
+            SortKey::Created => "created",
+            SortKey::Updated => "updated",
+            SortKey::Language => "language",
+        }
+    }
+}
+
+/// Current time as Unix epoch seconds.
+pub fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Format Unix epoch seconds as `YYYY-MM-DD HH:MM UTC`.
+pub fn format_timestamp(secs: u64) -> String {
+    match OffsetDateTime::from_unix_timestamp(secs as i64) {
+        Ok(dt) => format!(
+            "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+            dt.year(),
+            u8::from(dt.month()),
+            dt.day(),
+            dt.hour(),
+            dt.minute()
+        ),
+        Err(_) => secs.to_string(),
+    }
+}
+
+/// Per-user application data root (`.../.snipman`) for the current platform.
+///
+/// `init::user_dirs` keeps the install state here even when snippets are
+/// stored elsewhere.
+pub fn default_data_root() -> PathBuf {
     let path: PathBuf = match os::current_os() {
         os::OsKind::Windows => std::env::var_os("APPDATA")
             .map(PathBuf::from)
@@ -62,54 +238,49 @@ fn get_snippets_dir() -> PathBuf {
             .unwrap_or_else(|| PathBuf::from(".")),
     };
 
-    let path = path.join(".snipman").join("snippets");
-    path
+    path.join(".snipman")
 }
 
-/// Persist a snippet to disk as `<id>.json` in the snippets directory.
-/// Creates the directory if it doesn't exist.
-pub fn save_snippet(snippet: &Snippet) -> std::io::Result<()> {
-    let snippets_dir = get_snippets_dir();
-
-    fs::create_dir_all(&snippets_dir)?;
-
-    let file_path = snippets_dir.join(format!("{}.json", snippet.id));
-    let json_data = serde_json::to_string_pretty(snippet).expect("Failed to serialize snippet");
-
-    fs::write(file_path, json_data)?;
-
-    println!("Snippet '{}' saved successfully!", snippet.description);
-    Ok(())
+/// Root of all snippet data: `SNIPMAN_DATA_DIR`, else `data_dir` from the
+/// config file, else [`default_data_root`].
+pub fn get_data_root() -> PathBuf {
+    if let Some(dir) = std::env::var_os("SNIPMAN_DATA_DIR").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = &crate::config::get().data_dir {
+        return dir.clone();
+    }
+    default_data_root()
 }
 
-/// Load all snippets from disk, ignoring malformed entries with a warning.
-pub fn load_snippets() -> std::io::Result<Vec<Snippet>> {
-    let snippet_dir = get_snippets_dir();
-    fs::create_dir_all(&snippet_dir)?;
-
-    let mut snippets = Vec::new();
-    let entries = fs::read_dir(snippet_dir)?.filter_map(std::io::Result::ok);
+/// Directory holding the JSON snippet files of the active library.
+pub fn get_snippets_dir() -> PathBuf {
+    library::root(library::active()).join("snippets")
+}
 
-    for entry in entries {
-        let path = entry.path();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
-            let data = fs::read_to_string(&path)?;
-            match serde_json::from_str(&data) {
-                Ok(snippet) => snippets.push(snippet),
-                Err(e) => eprintln!("Failed to parse {}: {}", path.display(), e),
-            }
-        }
+    #[test]
+    fn loads_snippets_without_metadata() {
+        let json = r#"{"id":"1","description":"d","tags":[],"code":"c"}"#;
+        let snippet: Snippet = serde_json::from_str(json).unwrap();
+        assert!(snippet.created_at.is_none());
+        assert!(snippet.metadata_lines().is_empty());
     }
 
-    Ok(snippets)
-}
+    #[test]
+    fn sorts_newest_first_and_missing_language_last() {
+        let mut old = Snippet::new("b".into(), vec![], String::new());
+        old.created_at = Some(1);
+        let mut new = Snippet::new("a".into(), vec![], String::new());
+        new.created_at = Some(2);
+        new.language = Some("rust".into());
 
-/// Delete a snippet file by its `id`.
-pub fn delete_snippet(id: &str) -> std::io::Result<()> {
-    let path = get_snippets_dir().join(format!("{}.json", id));
-    if path.exists() {
-        fs::remove_file(path)?;
+        assert_eq!(SortKey::Created.compare(&new, &old), Ordering::Less);
+        assert_eq!(SortKey::Language.compare(&new, &old), Ordering::Less);
+        assert_eq!(SortKey::Description.compare(&old, &new), Ordering::Greater);
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
     }
-    Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,833 @@
+//! Storage backends for snippets.
+//!
+//! [`SnippetStore`] abstracts over where snippets live so the CLI and TUI never
+//! touch the filesystem directly. The default backend is [`JsonDirStore`], which
+//! keeps one prettified `<id>.json` file per snippet in the `snippets` directory
+//! of the active library.
+//!
+//! With `git.enabled` in the config file, the directory is also a git repository
+//! and every change is committed ([`git::GitStore`]).
+//!
+//! Inside a project with a `.snipman/` directory (see [`crate::project`]), its
+//! snippets are layered over the library by [`layered::LayeredStore`], as are
+//! the read-only `shared_libraries` from the config file.
+//!
+//! Building with the `sqlite` feature adds [`sqlite::SqliteStore`], selected by
+//! `backend = "sqlite"` in the config file or `SNIPMAN_BACKEND=sqlite`.
+
+pub mod git;
+pub mod layered;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::config;
+use crate::fsutil::{lock_path_for, move_file, write_atomic, DirLock};
+use crate::library;
+use crate::migrate::{self, Upgrade};
+use crate::project;
+use crate::snippets::{now_unix, Snippet};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// A place snippets can be read from and written to.
+pub trait SnippetStore {
+    /// Fetch a single snippet by id, or `None` if it does not exist.
+    fn get(&self, id: &str) -> io::Result<Option<Snippet>>;
+
+    /// Insert or replace a snippet, keyed by its id.
+    fn put(&mut self, snippet: &Snippet) -> io::Result<()>;
+
+    /// Delete a snippet by id. Deleting an unknown id is not an error.
+    ///
+    /// Backends with a trash keep the snippet there, see [`Self::trash`].
+    fn delete(&mut self, id: &str) -> io::Result<()>;
+
+    /// Return every stored snippet, in no particular order.
+    fn list(&self) -> io::Result<Vec<Snippet>>;
+
+    /// Return the snippets whose description, tags or code contain `query`
+    /// (case-insensitive).
+    ///
+    /// Backends with an index should override this; the default scans [`Self::list`].
+    fn query(&self, query: &str) -> io::Result<Vec<Snippet>> {
+        let needle = query.to_lowercase();
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|s| {
+                s.description.to_lowercase().contains(&needle)
+                    || s.tags.iter().any(|t| t.to_lowercase().contains(&needle))
+                    || s.code.to_lowercase().contains(&needle)
+            })
+            .collect())
+    }
+
+    /// Upgrade stored documents to the current schema version, see [`crate::migrate`].
+    ///
+    /// With `dry_run`, only report what would change. Backends that upgrade
+    /// lazily or have nothing to upgrade return an empty list.
+    fn migrate(&mut self, _dry_run: bool) -> io::Result<Vec<Upgrade>> {
+        Ok(Vec::new())
+    }
+
+    /// Earlier versions of snippet `id`, oldest first. Every `put` that changes
+    /// a snippet keeps the version it replaces.
+    ///
+    /// # Errors
+    /// `Unsupported` for backends that do not keep history.
+    fn history(&self, _id: &str) -> io::Result<Vec<Revision>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this storage backend does not keep revision history",
+        ))
+    }
+
+    /// Snippets deleted from this store that can still be restored, most
+    /// recently deleted first.
+    ///
+    /// # Errors
+    /// `Unsupported` for backends without a trash, whose deletions are permanent.
+    fn trash(&self) -> io::Result<Vec<Trashed>> {
+        Err(no_trash())
+    }
+
+    /// Move the most recently deleted copy of `id` out of the trash and return it.
+    ///
+    /// # Errors
+    /// `NotFound` if `id` is not in the trash, `AlreadyExists` if a snippet
+    /// with that id exists again.
+    fn restore_trashed(&mut self, _id: &str) -> io::Result<Snippet> {
+        Err(no_trash())
+    }
+
+    /// Permanently remove the trashed snippets deleted before `before` (Unix
+    /// seconds), or all of them with `None`. Returns how many were removed.
+    fn empty_trash(&mut self, _before: Option<u64>) -> io::Result<usize> {
+        Err(no_trash())
+    }
+
+    /// Whether [`Self::query`] is served by an index and is cheap enough to run
+    /// on every keystroke. Callers fall back to in-memory matching otherwise.
+    fn has_index(&self) -> bool {
+        false
+    }
+}
+
+/// An earlier version of a snippet, see [`SnippetStore::history`].
+pub struct Revision {
+    /// Position in the snippet's history, starting at 1 for the oldest.
+    pub number: u32,
+    /// The snippet as it was; `updated_at` tells when this version was written.
+    pub snippet: Snippet,
+}
+
+/// A deleted snippet waiting in the trash, see [`SnippetStore::trash`].
+pub struct Trashed {
+    pub snippet: Snippet,
+    /// When it was deleted, in Unix seconds.
+    pub deleted_at: u64,
+}
+
+fn no_trash() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "this storage backend deletes snippets permanently and has no trash",
+    )
+}
+
+/// Deletion time before which trashed snippets are purged, from
+/// `trash.retention_days`; `None` if they are kept forever.
+pub fn trash_cutoff() -> Option<u64> {
+    let days = config::get().trash.retention_days;
+    (days > 0).then(|| now_unix().saturating_sub(days.saturating_mul(86_400)))
+}
+
+/// Open the store of the active library (see [`crate::library`]), layered with
+/// project and shared snippets.
+pub fn open_store() -> io::Result<Box<dyn SnippetStore>> {
+    open_store_in(library::active())
+}
+
+/// Open library `name` with the current project's snippets layered on top and
+/// the configured shared libraries underneath.
+pub fn open_store_in(name: &str) -> io::Result<Box<dyn SnippetStore>> {
+    let store = open_library(name)?;
+    let project = project_store();
+    let shared: Vec<JsonDirStore> = config::get()
+        .shared_libraries
+        .iter()
+        .map(|dir| JsonDirStore::read_only(dir.clone()))
+        .collect();
+    if project.is_none() && shared.is_empty() {
+        return Ok(store);
+    }
+    Ok(Box::new(layered::LayeredStore::new(store, project, shared)))
+}
+
+/// The snippet store of the current project.
+///
+/// # Errors
+/// `NotFound` if neither the working directory nor a parent has a `.snipman` directory.
+pub fn open_project() -> io::Result<JsonDirStore> {
+    project_store().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "no {} directory in the current directory or its parents (create one with `mkdir {}`)",
+                project::DIR_NAME,
+                project::DIR_NAME
+            ),
+        )
+    })
+}
+
+/// The snippet files of the project containing the working directory, if any.
+fn project_store() -> Option<JsonDirStore> {
+    let dir = project::current()?;
+    Some(JsonDirStore::checked_in(
+        dir.join("snippets"),
+        project::state_dir(&dir),
+    ))
+}
+
+/// Where a library keeps its snippets, set with `backend` in the config file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// One JSON file per snippet ([`JsonDirStore`], or [`git::GitStore`] with `git.enabled`).
+    #[default]
+    Json,
+    /// A SQLite database with a full-text index; needs the `sqlite` feature.
+    Sqlite,
+}
+
+/// The configured backend, unless `SNIPMAN_BACKEND` overrides it.
+pub fn backend() -> io::Result<Backend> {
+    match env::var("SNIPMAN_BACKEND").unwrap_or_default().as_str() {
+        "" => Ok(config::get().backend),
+        "json" => Ok(Backend::Json),
+        "sqlite" => Ok(Backend::Sqlite),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown SNIPMAN_BACKEND '{}' (expected json or sqlite)",
+                other
+            ),
+        )),
+    }
+}
+
+/// Open the store of library `name`, without project snippets.
+///
+/// The backend is chosen by `backend` in the config file (`json`, the default,
+/// or `sqlite`); `SNIPMAN_BACKEND` overrides it.
+///
+/// # Errors
+/// `NotFound` if the library does not exist.
+pub fn open_library(name: &str) -> io::Result<Box<dyn SnippetStore>> {
+    if !library::exists(name) {
+        return Err(library::not_found(name));
+    }
+    let root = library::root(name);
+    let git = &config::get().git;
+    match backend()? {
+        Backend::Json if git.enabled => Ok(Box::new(git::GitStore::open(
+            root.join("snippets"),
+            &git.branch,
+        )?)),
+        Backend::Json => Ok(Box::new(JsonDirStore::new(root.join("snippets")))),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Ok(Box::new(sqlite::SqliteStore::open(
+            &root.join("snippets.db"),
+            &root.join("snippets"),
+        )?)),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "snipman was built without the `sqlite` feature",
+        )),
+    }
+}
+
+/// Parse a stored document, upgrading it in memory. Also returns the version it
+/// was upgraded from, if it was older than the current schema.
+fn parse_document(data: &str) -> io::Result<(Snippet, Option<u32>)> {
+    let mut value: Value =
+        serde_json::from_str(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let upgraded_from = migrate::upgrade(&mut value)?;
+    let snippet: Snippet =
+        serde_json::from_value(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((snippet, upgraded_from))
+}
+
+/// One prettified JSON file per snippet, named `<id>.json`.
+///
+/// Replaced versions are kept as `history/<id>/<n>.json`, numbered from 1.
+/// Deleted snippets are moved to `trash/<id>@<deleted_at>.json` and purged
+/// after `trash.retention_days`.
+///
+/// Writes are atomic (temp file + rename) and serialized across processes by
+/// the `.lock` file in the data root. A [`Self::checked_in`] store keeps
+/// history, trash, backups and the lock in its state directory instead.
+pub struct JsonDirStore {
+    dir: PathBuf,
+    /// Never write: old documents are only upgraded in memory and `put`/`delete` fail.
+    read_only: bool,
+    /// Set for directories checked into a project: history, trash, backups and
+    /// the lock live here instead.
+    state_dir: Option<PathBuf>,
+    /// Rewrite documents with an older schema when they are loaded. Otherwise
+    /// they are only upgraded in memory until `migrate` is run, so loading
+    /// never leaves changes in a repository.
+    rewrite_on_load: bool,
+}
+
+impl JsonDirStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            read_only: false,
+            state_dir: None,
+            rewrite_on_load: true,
+        }
+    }
+
+    /// A store over `dir` that never modifies it, e.g. a shared network mount.
+    pub fn read_only(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            read_only: true,
+            state_dir: None,
+            rewrite_on_load: false,
+        }
+    }
+
+    /// A store over `dir`, the work tree of a git repository, that only writes
+    /// when asked to so every change can be committed.
+    pub fn versioned(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            read_only: false,
+            state_dir: None,
+            rewrite_on_load: false,
+        }
+    }
+
+    /// A store over `dir`, a directory checked into a project's repository,
+    /// that only ever writes snippet files there. Everything else goes to
+    /// `state_dir`.
+    pub fn checked_in(dir: PathBuf, state_dir: PathBuf) -> Self {
+        Self {
+            dir,
+            read_only: false,
+            state_dir: Some(state_dir),
+            rewrite_on_load: false,
+        }
+    }
+
+    /// Parent of the `history`, `trash` and `backups` directories.
+    fn state_root(&self) -> &Path {
+        self.state_dir.as_deref().unwrap_or(&self.dir)
+    }
+
+    fn check_writable(&self) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is read-only", self.dir.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Path of the file backing `id`. Rejects ids that could escape the directory.
+    fn path_for(&self, id: &str) -> io::Result<PathBuf> {
+        if id.is_empty() || id.contains(['/', '\\']) || id == "." || id == ".." {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid snippet id '{}'", id),
+            ));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    /// Directory holding the earlier versions of `id`.
+    fn history_dir(&self, id: &str) -> io::Result<PathBuf> {
+        self.path_for(id)?;
+        Ok(self.state_root().join("history").join(id))
+    }
+
+    /// Revision numbers stored for `id`, ascending.
+    fn revision_numbers(&self, id: &str) -> io::Result<Vec<u32>> {
+        let mut numbers: Vec<u32> = match fs::read_dir(self.history_dir(id)?) {
+            Ok(entries) => entries
+                .filter_map(io::Result::ok)
+                .filter_map(|entry| {
+                    let name = entry.file_name();
+                    name.to_str()?.strip_suffix(".json")?.parse().ok()
+                })
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    /// Keep the current file of `id` as its next revision if `replacement` differs.
+    /// A file that is not a valid snippet (e.g. one with merge conflict markers)
+    /// is not kept. Must be called with the lock held.
+    fn record_revision(&self, id: &str, replacement: &str) -> io::Result<()> {
+        let current = match fs::read_to_string(self.path_for(id)?) {
+            Ok(current) => current,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if current == replacement || parse_document(&current).is_err() {
+            return Ok(());
+        }
+        let next = self.revision_numbers(id)?.last().map_or(1, |n| n + 1);
+        let dir = self.history_dir(id)?;
+        fs::create_dir_all(&dir)?;
+        write_atomic(&dir.join(format!("{}.json", next)), current.as_bytes())
+    }
+
+    fn trash_dir(&self) -> PathBuf {
+        self.state_root().join("trash")
+    }
+
+    /// Files in the trash with the id and deletion time from their names,
+    /// most recently deleted first.
+    fn trash_files(&self) -> io::Result<Vec<(String, u64, PathBuf)>> {
+        let entries = match fs::read_dir(self.trash_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut files: Vec<(String, u64, PathBuf)> = entries
+            .filter_map(io::Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                let name = path.file_name()?.to_str()?.strip_suffix(".json")?;
+                let (id, deleted_at) = name.rsplit_once('@')?;
+                Some((id.to_string(), deleted_at.parse().ok()?, path))
+            })
+            .collect();
+        files.sort_by_key(|(_, deleted_at, _)| std::cmp::Reverse(*deleted_at));
+        Ok(files)
+    }
+
+    /// Remove trashed files deleted before `before` (all with `None`), along
+    /// with the history of snippets that are now gone for good. Must be called
+    /// with the lock held.
+    fn purge_trash(&self, before: Option<u64>) -> io::Result<usize> {
+        let mut purged = Vec::new();
+        for (id, deleted_at, path) in self.trash_files()? {
+            if before.is_none_or(|before| deleted_at < before) {
+                fs::remove_file(path)?;
+                purged.push(id);
+            }
+        }
+        let remaining = self.trash_files()?;
+        for id in &purged {
+            if !self.path_for(id)?.exists() && !remaining.iter().any(|(other, ..)| other == id) {
+                match fs::remove_dir_all(self.history_dir(id)?) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+        Ok(purged.len())
+    }
+
+    /// Snippet files that cannot be loaded, with the reason. Nothing is rewritten.
+    pub fn unreadable(&self) -> io::Result<Vec<(PathBuf, io::Error)>> {
+        Ok(self
+            .json_files()?
+            .into_iter()
+            .filter_map(|path| {
+                let result = fs::read_to_string(&path).and_then(|data| parse_document(&data));
+                result.err().map(|e| (path, e))
+            })
+            .collect())
+    }
+
+    /// Paths of every `*.json` document in the directory.
+    fn json_files(&self) -> io::Result<Vec<PathBuf>> {
+        if self.read_only && !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        if !self.read_only {
+            fs::create_dir_all(&self.dir)?;
+        }
+        Ok(fs::read_dir(&self.dir)?
+            .filter_map(io::Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+            .collect())
+    }
+
+    /// Read one snippet file, upgrading it in place if it uses an older schema
+    /// and the store rewrites on load.
+    fn read_file(&self, path: &Path) -> io::Result<Snippet> {
+        let data = fs::read_to_string(path)?;
+        let (snippet, upgraded_from) = parse_document(&data)?;
+        if let Some(from) = upgraded_from
+            && self.rewrite_on_load
+        {
+            let _lock = self.lock()?;
+            self.rewrite_upgraded(path, &data, &snippet, from)?;
+        }
+        Ok(snippet)
+    }
+
+    /// Keep the original file under `backups/v<from>/` and write the upgraded
+    /// snippet. Must be called with the lock held.
+    fn rewrite_upgraded(
+        &self,
+        path: &Path,
+        original: &str,
+        snippet: &Snippet,
+        from: u32,
+    ) -> io::Result<()> {
+        let backup_dir = self.state_root().join("backups").join(format!("v{}", from));
+        fs::create_dir_all(&backup_dir)?;
+        if let Some(name) = path.file_name() {
+            write_atomic(&backup_dir.join(name), original.as_bytes())?;
+        }
+        let json_data = serde_json::to_string_pretty(snippet).map_err(io::Error::other)?;
+        write_atomic(path, json_data.as_bytes())
+    }
+
+    /// Take the lock guarding every write to this directory.
+    fn lock(&self) -> io::Result<DirLock> {
+        match &self.state_dir {
+            Some(state_dir) => DirLock::acquire(&state_dir.join(".lock")),
+            None => DirLock::acquire(&lock_path_for(&self.dir)),
+        }
+    }
+}
+
+impl SnippetStore for JsonDirStore {
+    fn get(&self, id: &str) -> io::Result<Option<Snippet>> {
+        let path = self.path_for(id)?;
+        if !path.is_file() {
+            return Ok(None);
+        }
+        self.read_file(&path).map(Some)
+    }
+
+    /// Creates the directory if it doesn't exist. The file is replaced atomically.
+    fn put(&mut self, snippet: &Snippet) -> io::Result<()> {
+        self.check_writable()?;
+        let _lock = self.lock()?;
+        self.put_locked(snippet)
+    }
+
+    /// Moves the file to the trash, then purges trashed snippets older than
+    /// `trash.retention_days`.
+    fn delete(&mut self, id: &str) -> io::Result<()> {
+        self.check_writable()?;
+        let _lock = self.lock()?;
+        self.delete_locked(id)
+    }
+
+    /// Malformed entries are skipped with a warning.
+    fn list(&self) -> io::Result<Vec<Snippet>> {
+        let mut snippets = Vec::new();
+        for path in self.json_files()? {
+            match self.read_file(&path) {
+                Ok(snippet) => snippets.push(snippet),
+                Err(e) => eprintln!("Failed to parse {}: {}", path.display(), e),
+            }
+        }
+        Ok(snippets)
+    }
+
+    /// Malformed revisions are skipped with a warning.
+    fn history(&self, id: &str) -> io::Result<Vec<Revision>> {
+        let dir = self.history_dir(id)?;
+        let mut revisions = Vec::new();
+        for number in self.revision_numbers(id)? {
+            let path = dir.join(format!("{}.json", number));
+            match fs::read_to_string(&path).and_then(|data| parse_document(&data)) {
+                Ok((snippet, _)) => revisions.push(Revision { number, snippet }),
+                Err(e) => eprintln!("Failed to parse {}: {}", path.display(), e),
+            }
+        }
+        Ok(revisions)
+    }
+
+    /// Malformed entries are skipped with a warning.
+    fn trash(&self) -> io::Result<Vec<Trashed>> {
+        let mut trashed = Vec::new();
+        for (_, deleted_at, path) in self.trash_files()? {
+            match fs::read_to_string(&path).and_then(|data| parse_document(&data)) {
+                Ok((snippet, _)) => trashed.push(Trashed {
+                    snippet,
+                    deleted_at,
+                }),
+                Err(e) => eprintln!("Failed to parse {}: {}", path.display(), e),
+            }
+        }
+        Ok(trashed)
+    }
+
+    fn restore_trashed(&mut self, id: &str) -> io::Result<Snippet> {
+        self.check_writable()?;
+        let _lock = self.lock()?;
+        self.restore_trashed_locked(id)
+    }
+
+    fn empty_trash(&mut self, before: Option<u64>) -> io::Result<usize> {
+        self.check_writable()?;
+        let _lock = self.lock()?;
+        self.purge_trash(before)
+    }
+
+    fn migrate(&mut self, dry_run: bool) -> io::Result<Vec<Upgrade>> {
+        if self.read_only {
+            return Ok(Vec::new());
+        }
+        let _lock = self.lock()?;
+        self.migrate_locked(dry_run)
+    }
+}
+
+impl JsonDirStore {
+    /// [`SnippetStore::put`] with the lock already held.
+    fn put_locked(&self, snippet: &Snippet) -> io::Result<()> {
+        let path = self.path_for(&snippet.id)?;
+        fs::create_dir_all(&self.dir)?;
+        let json_data = serde_json::to_string_pretty(snippet).map_err(io::Error::other)?;
+        self.record_revision(&snippet.id, &json_data)?;
+        write_atomic(&path, json_data.as_bytes())
+    }
+
+    /// [`SnippetStore::delete`] with the lock already held.
+    fn delete_locked(&self, id: &str) -> io::Result<()> {
+        let path = self.path_for(id)?;
+        if path.exists() {
+            let trash = self.trash_dir();
+            fs::create_dir_all(&trash)?;
+            move_file(&path, &trash.join(format!("{}@{}.json", id, now_unix())))?;
+        }
+        if let Some(cutoff) = trash_cutoff() {
+            self.purge_trash(Some(cutoff))?;
+        }
+        Ok(())
+    }
+
+    /// [`SnippetStore::restore_trashed`] with the lock already held.
+    fn restore_trashed_locked(&self, id: &str) -> io::Result<Snippet> {
+        let path = self.path_for(id)?;
+        let Some((_, _, trashed)) = self
+            .trash_files()?
+            .into_iter()
+            .find(|(trashed_id, ..)| trashed_id == id)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("snippet {} is not in the trash", id),
+            ));
+        };
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("snippet {} exists again; delete it before restoring", id),
+            ));
+        }
+        let (snippet, _) = parse_document(&fs::read_to_string(&trashed)?)?;
+        fs::create_dir_all(&self.dir)?;
+        move_file(&trashed, &path)?;
+        Ok(snippet)
+    }
+
+    /// [`SnippetStore::migrate`] with the lock already held.
+    fn migrate_locked(&self, dry_run: bool) -> io::Result<Vec<Upgrade>> {
+        let mut upgrades = Vec::new();
+        for path in self.json_files()? {
+            let data = fs::read_to_string(&path)?;
+            let mut value: Value = match serde_json::from_str(&data) {
+                Ok(value) => value,
+                Err(e) => {
+                    eprintln!("Failed to parse {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            match migrate::upgrade(&mut value) {
+                Ok(Some(from)) => {
+                    if !dry_run {
+                        let snippet: Snippet = serde_json::from_value(value)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                        self.rewrite_upgraded(&path, &data, &snippet, from)?;
+                    }
+                    upgrades.push(Upgrade {
+                        item: path.display().to_string(),
+                        from,
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        Ok(upgrades)
+    }
+}
+
+/// Volatile store used by unit tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    snippets: std::collections::BTreeMap<String, Snippet>,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn with(snippets: Vec<Snippet>) -> Self {
+        Self {
+            snippets: snippets.into_iter().map(|s| (s.id.clone(), s)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl SnippetStore for MemoryStore {
+    fn get(&self, id: &str) -> io::Result<Option<Snippet>> {
+        Ok(self.snippets.get(id).cloned())
+    }
+
+    fn put(&mut self, snippet: &Snippet) -> io::Result<()> {
+        self.snippets.insert(snippet.id.clone(), snippet.clone());
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &str) -> io::Result<()> {
+        self.snippets.remove(id);
+        Ok(())
+    }
+
+    fn list(&self) -> io::Result<Vec<Snippet>> {
+        Ok(self.snippets.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fsutil::TempDir;
+
+    /// A store in a fresh temporary data root, returned alongside that root.
+    fn temp_store() -> (JsonDirStore, TempDir) {
+        let root = TempDir::new();
+        (JsonDirStore::new(root.join("snippets")), root)
+    }
+
+    #[test]
+    fn json_dir_store_roundtrip() {
+        let (mut store, root) = temp_store();
+        let snippet = Snippet::new("Read file".into(), vec!["fs".into()], "cat x".into());
+
+        store.put(&snippet).unwrap();
+        assert_eq!(store.get(&snippet.id).unwrap().unwrap().code, "cat x");
+        assert_eq!(store.query("FS").unwrap().len(), 1);
+        assert!(store.query("nothing").unwrap().is_empty());
+
+        store.delete(&snippet.id).unwrap();
+        assert!(store.get(&snippet.id).unwrap().is_none());
+        assert!(store.list().unwrap().is_empty());
+        assert!(root.join(".lock").exists());
+    }
+
+    #[test]
+    fn json_dir_store_keeps_replaced_versions() {
+        let (mut store, root) = temp_store();
+        let mut snippet = Snippet::new("Greet".into(), vec![], "echo hi".into());
+        store.put(&snippet).unwrap();
+        store.put(&snippet).unwrap();
+        assert!(store.history(&snippet.id).unwrap().is_empty());
+
+        snippet.code = "echo hello".into();
+        store.put(&snippet).unwrap();
+        snippet.code = "echo hey".into();
+        store.put(&snippet).unwrap();
+        let history = store.history(&snippet.id).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].number, 1);
+        assert_eq!(history[0].snippet.code, "echo hi");
+        assert_eq!(history[1].snippet.code, "echo hello");
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        let history_dir = root.join("snippets/history").join(&snippet.id);
+        fs::write(history_dir.join("3.json"), "<<<<<<< HEAD").unwrap();
+        assert_eq!(store.history(&snippet.id).unwrap().len(), 2);
+        fs::write(
+            root.join("snippets").join(format!("{}.json", snippet.id)),
+            "{",
+        )
+        .unwrap();
+        snippet.code = "echo hey there".into();
+        store.put(&snippet).unwrap();
+        assert_eq!(store.history(&snippet.id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn deleted_snippets_wait_in_the_trash() {
+        let (mut store, root) = temp_store();
+        let mut snippet = Snippet::new("Greet".into(), vec![], "echo hi".into());
+        store.put(&snippet).unwrap();
+        snippet.code = "echo hello".into();
+        store.put(&snippet).unwrap();
+        store.delete(&snippet.id).unwrap();
+        assert!(store.list().unwrap().is_empty());
+        let trashed = store.trash().unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].snippet.code, "echo hello");
+
+        assert_eq!(
+            store.restore_trashed(&snippet.id).unwrap().code,
+            "echo hello"
+        );
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert!(store.trash().unwrap().is_empty());
+        assert!(store.restore_trashed(&snippet.id).is_err());
+
+        store.delete(&snippet.id).unwrap();
+        assert_eq!(store.empty_trash(Some(0)).unwrap(), 0);
+        assert_eq!(store.empty_trash(None).unwrap(), 1);
+        assert!(store.trash().unwrap().is_empty());
+        assert!(!root.join("snippets/history").join(&snippet.id).exists());
+    }
+
+    #[test]
+    fn checked_in_store_only_writes_snippet_files() {
+        let (_, root) = temp_store();
+        let dir = root.join("repo/.snipman/snippets");
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = r#"{"id":"t1","description":"old","tags":[],"code":"ls"}"#;
+        fs::write(dir.join("t1.json"), legacy).unwrap();
+
+        let mut store = JsonDirStore::checked_in(dir.clone(), root.join("state"));
+        assert_eq!(store.list().unwrap()[0].description, "old");
+        assert_eq!(fs::read_to_string(dir.join("t1.json")).unwrap(), legacy);
+
+        let mut snippet = store.get("t1").unwrap().unwrap();
+        snippet.code = "ls -la".into();
+        store.put(&snippet).unwrap();
+        store.delete("t1").unwrap();
+        assert_eq!(store.trash().unwrap().len(), 1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        assert!(!root.join("repo/.snipman/.lock").exists());
+        assert!(root.join("state/history/t1").is_dir());
+    }
+
+    #[test]
+    fn json_dir_store_rejects_path_ids() {
+        let (store, _) = temp_store();
+        assert!(store.get("../secret").is_err());
+    }
+}
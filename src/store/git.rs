@@ -0,0 +1,549 @@
+//! Snippet directory kept in a git repository (enabled with `git.enabled`).
+//!
+//! [`GitStore`] wraps a [`JsonDirStore`] and commits every change with a
+//! message naming the snippet. [`sync`] merges with a remote and pushes back;
+//! conflicting snippet files are handed to a resolver one snippet at a time
+//! instead of leaving merge markers in the JSON.
+//!
+//! Only the snippet files are versioned: a `.gitignore` keeps the per-machine
+//! history, trash and backup directories out of the repository. Files with an
+//! older schema are upgraded in memory when loaded; `migrate` rewrites and
+//! commits them.
+//!
+//! Every write and its commit happen under the directory lock, so concurrent
+//! snipman processes never race on git's index.
+//!
+//! Everything shells out to the `git` command-line tool.
+
+use crate::fsutil::{lock_path_for, write_atomic, DirLock};
+use crate::migrate::{self, Upgrade};
+use crate::snippets::Snippet;
+use crate::store::{parse_document, JsonDirStore, Revision, SnippetStore, Trashed};
+use clap::ValueEnum;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use uuid::Uuid;
+
+/// Run `git` in `dir` and return its stdout.
+///
+/// # Errors
+/// `Other` with git's stderr if it exits unsuccessfully, `NotFound` if git is
+/// not installed.
+fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = git_output(dir, args)?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Run `git` in `dir` and report only whether it succeeded.
+fn git_ok(dir: &Path, args: &[&str]) -> io::Result<bool> {
+    Ok(git_output(dir, args)?.status.success())
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> io::Result<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                io::ErrorKind::NotFound,
+                "git is not installed or not on PATH",
+            ),
+            _ => e,
+        })
+}
+
+/// Contents of the `.gitignore` written into the snippet directory.
+const GITIGNORE: &str = "\
+# Kept per machine by snipman, not synced
+history/
+trash/
+backups/
+.*.tmp*
+";
+
+/// Make `dir` a git repository on `branch` if it is not one yet, committing
+/// any snippets already in it.
+pub fn init_repo(dir: &Path, branch: &str) -> io::Result<()> {
+    if dir.join(".git").exists() {
+        if ignore_local_state(dir)? {
+            commit_all(dir, "Stop tracking snippet history, trash and backups")?;
+        }
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir)?;
+    git(dir, &["init", "-q", "-b", branch])?;
+    if !git_ok(dir, &["config", "user.email"])? {
+        git(dir, &["config", "user.name", "snipman"])?;
+        git(dir, &["config", "user.email", "snipman@localhost"])?;
+    }
+    ignore_local_state(dir)?;
+    commit_all(dir, "Import existing snippets")?;
+    Ok(())
+}
+
+/// Write the [`GITIGNORE`] file if it is missing, untracking anything it
+/// covers that an older repository committed. Returns whether it was written.
+fn ignore_local_state(dir: &Path) -> io::Result<bool> {
+    let path = dir.join(".gitignore");
+    if path.exists() {
+        return Ok(false);
+    }
+    write_atomic(&path, GITIGNORE.as_bytes())?;
+    git(
+        dir,
+        &[
+            "rm",
+            "-r",
+            "-q",
+            "--cached",
+            "--ignore-unmatch",
+            "--",
+            "history",
+            "trash",
+            "backups",
+        ],
+    )?;
+    Ok(true)
+}
+
+/// Stage everything in `dir` and commit it, unless nothing changed.
+/// Returns whether a commit was made.
+fn commit_all(dir: &Path, message: &str) -> io::Result<bool> {
+    git(dir, &["add", "-A", "."])?;
+    if git_ok(dir, &["diff", "--cached", "--quiet"])? {
+        return Ok(false);
+    }
+    git(dir, &["commit", "-q", "-m", message])?;
+    Ok(true)
+}
+
+/// A [`JsonDirStore`] whose directory is a git repository; every write is a commit.
+pub struct GitStore {
+    inner: JsonDirStore,
+    dir: PathBuf,
+}
+
+impl GitStore {
+    /// Open the snippet directory `dir`, initializing the repository on `branch` if needed.
+    pub fn open(dir: PathBuf, branch: &str) -> io::Result<Self> {
+        {
+            let _lock = DirLock::acquire(&lock_path_for(&dir))?;
+            init_repo(&dir, branch)?;
+        }
+        Ok(Self {
+            inner: JsonDirStore::versioned(dir.clone()),
+            dir,
+        })
+    }
+}
+
+impl SnippetStore for GitStore {
+    fn get(&self, id: &str) -> io::Result<Option<Snippet>> {
+        self.inner.get(id)
+    }
+
+    fn put(&mut self, snippet: &Snippet) -> io::Result<()> {
+        let _lock = self.inner.lock()?;
+        let verb = if self.inner.get(&snippet.id)?.is_some() {
+            "Edit"
+        } else {
+            "Add"
+        };
+        self.inner.put_locked(snippet)?;
+        commit_all(&self.dir, &format!("{} '{}'", verb, snippet.description))?;
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &str) -> io::Result<()> {
+        let _lock = self.inner.lock()?;
+        let Some(snippet) = self.inner.get(id)? else {
+            return Ok(());
+        };
+        self.inner.delete_locked(id)?;
+        commit_all(&self.dir, &format!("Delete '{}'", snippet.description))?;
+        Ok(())
+    }
+
+    fn list(&self) -> io::Result<Vec<Snippet>> {
+        self.inner.list()
+    }
+
+    fn history(&self, id: &str) -> io::Result<Vec<Revision>> {
+        self.inner.history(id)
+    }
+
+    fn trash(&self) -> io::Result<Vec<Trashed>> {
+        self.inner.trash()
+    }
+
+    fn restore_trashed(&mut self, id: &str) -> io::Result<Snippet> {
+        let _lock = self.inner.lock()?;
+        let snippet = self.inner.restore_trashed_locked(id)?;
+        commit_all(&self.dir, &format!("Restore '{}'", snippet.description))?;
+        Ok(snippet)
+    }
+
+    fn empty_trash(&mut self, before: Option<u64>) -> io::Result<usize> {
+        let _lock = self.inner.lock()?;
+        let purged = self.inner.purge_trash(before)?;
+        commit_all(&self.dir, "Empty trash")?;
+        Ok(purged)
+    }
+
+    fn migrate(&mut self, dry_run: bool) -> io::Result<Vec<Upgrade>> {
+        let _lock = self.inner.lock()?;
+        let upgrades = self.inner.migrate_locked(dry_run)?;
+        if !dry_run && !upgrades.is_empty() {
+            commit_all(
+                &self.dir,
+                &format!(
+                    "Upgrade {} snippets to schema version {}",
+                    upgrades.len(),
+                    migrate::CURRENT_SCHEMA_VERSION
+                ),
+            )?;
+        }
+        Ok(upgrades)
+    }
+}
+
+/// Both sides of a snippet changed differently on the remote and locally.
+pub struct Conflict {
+    pub id: String,
+    /// The local version, or `None` if it was deleted locally.
+    pub mine: Option<Snippet>,
+    /// The remote version, or `None` if it was deleted on the remote.
+    pub theirs: Option<Snippet>,
+}
+
+/// How to settle a [`Conflict`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Resolution {
+    /// Keep the local version.
+    Mine,
+    /// Take the remote version.
+    Theirs,
+    /// Keep the local version and add the remote one as a new snippet.
+    Both,
+}
+
+/// What [`sync`] did.
+pub struct SyncReport {
+    /// Remote commits merged in.
+    pub pulled: usize,
+    /// Local commits pushed.
+    pub pushed: usize,
+    /// Conflicting snippets that were resolved.
+    pub resolved: usize,
+}
+
+/// Pull from and push to `remote`/`branch`, committing local changes first.
+///
+/// When the remote is set, the repository's `origin` is pointed at it.
+/// Conflicting snippets are passed to `resolve`; returning an error aborts the
+/// merge and leaves the repository as it was before the pull. Other snipman
+/// processes wait until the sync is done.
+pub fn sync(
+    dir: &Path,
+    remote: Option<&str>,
+    branch: &str,
+    resolve: &mut dyn FnMut(&Conflict) -> io::Result<Resolution>,
+) -> io::Result<SyncReport> {
+    let _lock = DirLock::acquire(&lock_path_for(dir))?;
+    init_repo(dir, branch)?;
+    commit_all(dir, "Commit local changes")?;
+    match remote {
+        Some(url) if git_ok(dir, &["remote", "get-url", "origin"])? => {
+            git(dir, &["remote", "set-url", "origin", url])?;
+        }
+        Some(url) => {
+            git(dir, &["remote", "add", "origin", url])?;
+        }
+        None if !git_ok(dir, &["remote", "get-url", "origin"])? => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no remote configured (pass --remote or set git.remote)",
+            ));
+        }
+        None => {}
+    }
+
+    git(dir, &["fetch", "-q", "origin"])?;
+    let tracking = format!("refs/remotes/origin/{}", branch);
+    let has_remote_branch = git_ok(dir, &["rev-parse", "--verify", "-q", &tracking])?;
+    let has_head = git_ok(dir, &["rev-parse", "--verify", "-q", "HEAD"])?;
+
+    let mut report = SyncReport {
+        pulled: 0,
+        pushed: 0,
+        resolved: 0,
+    };
+    if has_remote_branch {
+        let incoming = if has_head {
+            format!("HEAD..{}", tracking)
+        } else {
+            tracking.clone()
+        };
+        report.pulled = count(dir, &incoming)?;
+        if report.pulled > 0 {
+            report.resolved = merge(dir, &tracking, resolve)?;
+        }
+    }
+
+    if git_ok(dir, &["rev-parse", "--verify", "-q", "HEAD"])? {
+        report.pushed = if has_remote_branch {
+            count(dir, &format!("{}..HEAD", tracking))?
+        } else {
+            count(dir, "HEAD")?
+        };
+        if report.pushed > 0 {
+            git(dir, &["push", "-q", "origin", &format!("HEAD:{}", branch)])?;
+        }
+    }
+    Ok(report)
+}
+
+/// Number of commits in `range`.
+fn count(dir: &Path, range: &str) -> io::Result<usize> {
+    git(dir, &["rev-list", "--count", range])?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Merge `tracking` into HEAD, settling conflicting snippets with `resolve`.
+/// Returns the number of conflicts resolved.
+fn merge(
+    dir: &Path,
+    tracking: &str,
+    resolve: &mut dyn FnMut(&Conflict) -> io::Result<Resolution>,
+) -> io::Result<usize> {
+    if git_ok(
+        dir,
+        &[
+            "merge",
+            "-q",
+            "--no-edit",
+            "--allow-unrelated-histories",
+            tracking,
+        ],
+    )? {
+        return Ok(0);
+    }
+    let conflicted = git(dir, &["diff", "--name-only", "--diff-filter=U"])?;
+    let paths: Vec<&str> = conflicted.lines().filter(|l| !l.is_empty()).collect();
+    if paths.is_empty() {
+        let _ = git(dir, &["merge", "--abort"]);
+        return Err(io::Error::other(format!(
+            "could not merge {} (is the working tree clean?)",
+            tracking
+        )));
+    }
+    match resolve_all(dir, &paths, resolve) {
+        Ok(()) => {
+            git(dir, &["commit", "-q", "--no-edit"])?;
+            Ok(paths.len())
+        }
+        Err(e) => {
+            let _ = git(dir, &["merge", "--abort"]);
+            Err(e)
+        }
+    }
+}
+
+/// Settle every conflicted path. Snippet files (`<id>.json` at the top level)
+/// go to `resolve`; anything else keeps the local version.
+fn resolve_all(
+    dir: &Path,
+    paths: &[&str],
+    resolve: &mut dyn FnMut(&Conflict) -> io::Result<Resolution>,
+) -> io::Result<()> {
+    for path in paths {
+        let Some(id) = path.strip_suffix(".json").filter(|id| !id.contains('/')) else {
+            keep_local(dir, path)?;
+            continue;
+        };
+        let stage = |n: u8| -> io::Result<Option<Snippet>> {
+            let spec = format!(":{}:{}", n, path);
+            if !git_ok(dir, &["cat-file", "-e", &spec])? {
+                return Ok(None);
+            }
+            parse_document(&git(dir, &["show", &spec])?).map(|(snippet, _)| Some(snippet))
+        };
+        let conflict = Conflict {
+            id: id.to_string(),
+            mine: stage(2)?,
+            theirs: stage(3)?,
+        };
+        let (keep, extra) = match resolve(&conflict)? {
+            Resolution::Mine => (conflict.mine, None),
+            Resolution::Theirs => (conflict.theirs, None),
+            Resolution::Both => match (conflict.mine, conflict.theirs) {
+                (Some(mine), Some(mut theirs)) => {
+                    theirs.id = Uuid::new_v4().to_string();
+                    (Some(mine), Some(theirs))
+                }
+                (mine, theirs) => (mine.or(theirs), None),
+            },
+        };
+        // Written directly: going through the store would keep the file with
+        // the merge markers as a revision.
+        match keep {
+            Some(snippet) => {
+                write_snippet(dir, path, &snippet)?;
+                git(dir, &["add", "--", path])?;
+            }
+            None => {
+                git(dir, &["rm", "-q", "--ignore-unmatch", "--", path])?;
+            }
+        }
+        if let Some(snippet) = extra {
+            let path = format!("{}.json", snippet.id);
+            write_snippet(dir, &path, &snippet)?;
+            git(dir, &["add", "--", &path])?;
+        }
+    }
+    Ok(())
+}
+
+fn write_snippet(dir: &Path, path: &str, snippet: &Snippet) -> io::Result<()> {
+    let json_data = serde_json::to_string_pretty(snippet).map_err(io::Error::other)?;
+    write_atomic(&dir.join(path), json_data.as_bytes())
+}
+
+/// Settle a conflicted non-snippet file with the local version.
+fn keep_local(dir: &Path, path: &str) -> io::Result<()> {
+    if git_ok(dir, &["cat-file", "-e", &format!(":2:{}", path)])? {
+        git(dir, &["checkout", "--ours", "--", path])?;
+        git(dir, &["add", "--", path])?;
+    } else {
+        git(dir, &["rm", "-q", "--ignore-unmatch", "--", path])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsutil::TempDir;
+    use std::fs;
+
+    #[test]
+    fn sync_merges_and_resolves_conflicting_snippets() {
+        let root = TempDir::new();
+        let remote = root.join("remote.git");
+        fs::create_dir_all(&remote).unwrap();
+        git(&remote, &["init", "-q", "--bare"]).unwrap();
+        let url = remote.to_string_lossy().into_owned();
+        let mut keep_mine = |_: &Conflict| Ok(Resolution::Mine);
+
+        let mut laptop = GitStore::open(root.join("laptop"), "main").unwrap();
+        let mut snippet = Snippet::new("deploy".into(), vec![], "make deploy".into());
+        laptop.put(&snippet).unwrap();
+        let report = sync(&laptop.dir, Some(&url), "main", &mut keep_mine).unwrap();
+        // The import commit (with the .gitignore) and the new snippet.
+        assert_eq!((report.pulled, report.pushed), (0, 2));
+
+        let mut desktop = GitStore::open(root.join("desktop"), "main").unwrap();
+        sync(&desktop.dir, Some(&url), "main", &mut keep_mine).unwrap();
+        assert_eq!(
+            desktop.get(&snippet.id).unwrap().unwrap().code,
+            "make deploy"
+        );
+
+        snippet.code = "make deploy ENV=prod".into();
+        desktop.put(&snippet).unwrap();
+        sync(&desktop.dir, None, "main", &mut keep_mine).unwrap();
+        snippet.code = "make deploy -j8".into();
+        laptop.put(&snippet).unwrap();
+
+        let mut seen = None;
+        let report = sync(&laptop.dir, None, "main", &mut |c: &Conflict| {
+            seen = c.theirs.as_ref().map(|s| s.code.clone());
+            Ok(Resolution::Both)
+        })
+        .unwrap();
+        assert_eq!(report.resolved, 1);
+        assert_eq!(seen.as_deref(), Some("make deploy ENV=prod"));
+        let codes: Vec<String> = laptop.list().unwrap().into_iter().map(|s| s.code).collect();
+        assert_eq!(codes.len(), 2);
+        assert!(codes.contains(&"make deploy -j8".to_string()));
+        assert!(codes.contains(&"make deploy ENV=prod".to_string()));
+
+        // Revisions stay local, so only the snippet itself can conflict.
+        sync(&desktop.dir, None, "main", &mut keep_mine).unwrap();
+        for (store, codes) in [(&mut desktop, ["d1", "d2"]), (&mut laptop, ["l1", "l2"])] {
+            for code in codes {
+                snippet.code = code.into();
+                store.put(&snippet).unwrap();
+            }
+        }
+        sync(&desktop.dir, None, "main", &mut keep_mine).unwrap();
+        let report = sync(&laptop.dir, None, "main", &mut keep_mine).unwrap();
+        assert_eq!(report.resolved, 1);
+        assert_eq!(laptop.get(&snippet.id).unwrap().unwrap().code, "l2");
+        assert_eq!(laptop.history(&snippet.id).unwrap().len(), 3);
+        assert_eq!(git(&laptop.dir, &["status", "--porcelain"]).unwrap(), "");
+        assert_eq!(git(&laptop.dir, &["ls-files", "history"]).unwrap(), "");
+    }
+
+    #[test]
+    fn concurrent_writes_each_get_a_commit() {
+        let root = TempDir::new();
+        let dir = root.join("snippets");
+        GitStore::open(dir.clone(), "main").unwrap();
+        let writers: Vec<_> = (0..4)
+            .map(|n| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    let mut store = GitStore::open(dir, "main").unwrap();
+                    for i in 0..5 {
+                        let code = format!("echo {} {}", n, i);
+                        store
+                            .put(&Snippet::new(code.clone(), vec![], code))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(count(&dir, "HEAD").unwrap(), 21);
+        assert_eq!(git(&dir, &["status", "--porcelain"]).unwrap(), "");
+    }
+
+    #[test]
+    fn old_files_are_only_rewritten_by_migrate() {
+        let root = TempDir::new();
+        let dir = root.join("snippets");
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = r#"{"id":"t1","description":"old","tags":[],"code":"ls"}"#;
+        fs::write(dir.join("t1.json"), legacy).unwrap();
+        let mut store = GitStore::open(dir.clone(), "main").unwrap();
+
+        assert_eq!(store.list().unwrap()[0].description, "old");
+        let added = Snippet::new("new".into(), vec![], "pwd".into());
+        store.put(&added).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("t1.json")).unwrap(), legacy);
+        assert_eq!(
+            git(&dir, &["show", "--name-only", "--format=", "HEAD"]).unwrap(),
+            format!("{}.json\n", added.id)
+        );
+
+        assert_eq!(store.migrate(false).unwrap().len(), 1);
+        assert_ne!(fs::read_to_string(dir.join("t1.json")).unwrap(), legacy);
+        assert_eq!(git(&dir, &["status", "--porcelain"]).unwrap(), "");
+    }
+}
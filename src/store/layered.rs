@@ -0,0 +1,327 @@
+//! Project and shared snippets layered around the user's library.
+//!
+//! Listing merges every layer. When several hold a snippet with the same id,
+//! the first of project, user library and shared libraries (in config order)
+//! wins, so forking a shared snippet into the user library overrides it.
+//! Queries use each layer's own [`SnippetStore::query`], so the user library's
+//! index still serves them.
+//! Writes go back to the layer the snippet was loaded from, as recorded in
+//! [`Snippet::origin`]; new snippets go to the user's library. Shared layers
+//! are read-only.
+
+use crate::migrate::Upgrade;
+use crate::snippets::{Origin, Snippet};
+use crate::store::{JsonDirStore, Revision, SnippetStore, Trashed};
+use std::collections::HashSet;
+use std::io;
+
+pub struct LayeredStore {
+    user: Box<dyn SnippetStore>,
+    project: Option<JsonDirStore>,
+    shared: Vec<JsonDirStore>,
+}
+
+impl LayeredStore {
+    pub fn new(
+        user: Box<dyn SnippetStore>,
+        project: Option<JsonDirStore>,
+        shared: Vec<JsonDirStore>,
+    ) -> Self {
+        Self {
+            user,
+            project,
+            shared,
+        }
+    }
+
+    /// Every layer in precedence order, with the origin of its snippets.
+    fn layers(&self) -> Vec<(&dyn SnippetStore, Origin)> {
+        let mut layers: Vec<(&dyn SnippetStore, Origin)> = Vec::new();
+        if let Some(project) = &self.project {
+            layers.push((project, Origin::Project));
+        }
+        layers.push((self.user.as_ref(), Origin::User));
+        layers.extend(
+            self.shared
+                .iter()
+                .map(|store| (store as &dyn SnippetStore, Origin::Shared)),
+        );
+        layers
+    }
+}
+
+/// Whether one of `layers` holds `id`, hiding the copies in the layers below.
+fn shadowed(layers: &[(&dyn SnippetStore, Origin)], id: &str) -> io::Result<bool> {
+    for (store, _) in layers {
+        if store.get(id)?.is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn read_only(id: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "snippet {} is in a read-only shared library; fork it to your library first",
+            id
+        ),
+    )
+}
+
+fn no_project(id: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "snippet {} belongs to a project; run this inside the project directory",
+            id
+        ),
+    )
+}
+
+impl SnippetStore for LayeredStore {
+    fn get(&self, id: &str) -> io::Result<Option<Snippet>> {
+        for (store, origin) in self.layers() {
+            if let Some(mut snippet) = store.get(id)? {
+                snippet.origin = origin;
+                return Ok(Some(snippet));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Project snippets can only be written from inside their project.
+    fn put(&mut self, snippet: &Snippet) -> io::Result<()> {
+        match (snippet.origin, &mut self.project) {
+            (Origin::Project, Some(project)) => project.put(snippet),
+            (Origin::Project, None) => Err(no_project(&snippet.id)),
+            (Origin::Shared, _) => Err(read_only(&snippet.id)),
+            (Origin::User, _) => self.user.put(snippet),
+        }
+    }
+
+    /// Deletes the copy that is listed: the project's, else the user's. Snippets
+    /// that only exist in a shared library cannot be deleted.
+    fn delete(&mut self, id: &str) -> io::Result<()> {
+        match self.get(id)?.map(|s| s.origin) {
+            Some(Origin::Project) => match &mut self.project {
+                Some(project) => project.delete(id),
+                None => Ok(()),
+            },
+            Some(Origin::Shared) => Err(read_only(id)),
+            Some(Origin::User) | None => self.user.delete(id),
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<Snippet>> {
+        let mut snippets = Vec::new();
+        let mut seen = HashSet::new();
+        for (store, origin) in self.layers() {
+            for mut snippet in store.list()? {
+                if seen.insert(snippet.id.clone()) {
+                    snippet.origin = origin;
+                    snippets.push(snippet);
+                }
+            }
+        }
+        Ok(snippets)
+    }
+
+    /// Hits of every layer, layer by layer, keeping only the copies [`Self::list`]
+    /// would show.
+    fn query(&self, query: &str) -> io::Result<Vec<Snippet>> {
+        let layers = self.layers();
+        let mut hits = Vec::new();
+        for (i, (store, origin)) in layers.iter().enumerate() {
+            for mut snippet in store.query(query)? {
+                if !shadowed(&layers[..i], &snippet.id)? {
+                    snippet.origin = *origin;
+                    hits.push(snippet);
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    /// History of the copy that is listed.
+    fn history(&self, id: &str) -> io::Result<Vec<Revision>> {
+        for (store, _) in self.layers() {
+            if store.get(id)?.is_some() {
+                return store.history(id);
+            }
+        }
+        self.user.history(id)
+    }
+
+    /// Trash of the project and user layers; shared layers have none.
+    fn trash(&self) -> io::Result<Vec<Trashed>> {
+        let mut trashed = Vec::new();
+        if let Some(project) = &self.project {
+            trashed.extend(project.trash()?.into_iter().map(|mut t| {
+                t.snippet.origin = Origin::Project;
+                t
+            }));
+        }
+        trashed.extend(self.user.trash()?);
+        trashed.sort_by_key(|t| std::cmp::Reverse(t.deleted_at));
+        Ok(trashed)
+    }
+
+    /// Restores into the layer the snippet was deleted from, the project's first.
+    fn restore_trashed(&mut self, id: &str) -> io::Result<Snippet> {
+        if let Some(project) = &mut self.project {
+            match project.restore_trashed(id) {
+                Ok(mut snippet) => {
+                    snippet.origin = Origin::Project;
+                    return Ok(snippet);
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.user.restore_trashed(id)
+    }
+
+    fn empty_trash(&mut self, before: Option<u64>) -> io::Result<usize> {
+        let purged = match &mut self.project {
+            Some(project) => project.empty_trash(before)?,
+            None => 0,
+        };
+        Ok(purged + self.user.empty_trash(before)?)
+    }
+
+    /// Upgrades the project and user layers; shared layers are left alone.
+    fn migrate(&mut self, dry_run: bool) -> io::Result<Vec<Upgrade>> {
+        let mut upgrades = match &mut self.project {
+            Some(project) => project.migrate(dry_run)?,
+            None => Vec::new(),
+        };
+        upgrades.extend(self.user.migrate(dry_run)?);
+        Ok(upgrades)
+    }
+
+    /// The user's library holds most snippets; the other layers are scanned.
+    fn has_index(&self) -> bool {
+        self.user.has_index()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsutil::TempDir;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn project_entries_win_and_writes_stay_in_their_layer() {
+        let dir = TempDir::new();
+        let mut shared = Snippet::new("deploy".into(), vec![], "make deploy".into());
+        let mine = Snippet::new("mine".into(), vec![], "ls".into());
+        let user = MemoryStore::with(vec![shared.clone(), mine]);
+        shared.code = "make deploy ENV=prod".into();
+        let mut project = JsonDirStore::new(dir.join("snippets"));
+        project.put(&shared).unwrap();
+
+        let mut store = LayeredStore::new(Box::new(user), Some(project), Vec::new());
+        let listed = store.list().unwrap();
+        assert_eq!(listed.len(), 2);
+        let deploy = listed.iter().find(|s| s.id == shared.id).unwrap();
+        assert_eq!(deploy.code, "make deploy ENV=prod");
+        assert_eq!(deploy.origin, Origin::Project);
+
+        let mut edited = deploy.clone();
+        edited.description = "deploy (team)".into();
+        store.put(&edited).unwrap();
+        store
+            .put(&Snippet::new("new".into(), vec![], "pwd".into()))
+            .unwrap();
+        let project = store.project.as_ref().unwrap();
+        assert_eq!(project.list().unwrap().len(), 1);
+        assert_eq!(project.list().unwrap()[0].description, "deploy (team)");
+        assert_eq!(store.user.list().unwrap().len(), 3);
+
+        store.delete(&shared.id).unwrap();
+        assert_eq!(store.get(&shared.id).unwrap().unwrap().code, "make deploy");
+    }
+
+    #[test]
+    fn shared_entries_are_read_only_until_forked() {
+        let dir = TempDir::new();
+        let team = Snippet::new("team".into(), vec![], "make".into());
+        JsonDirStore::new(dir.to_path_buf()).put(&team).unwrap();
+
+        let mut store = LayeredStore::new(
+            Box::new(MemoryStore::default()),
+            None,
+            vec![JsonDirStore::read_only(dir.to_path_buf())],
+        );
+        let mut listed = store.get(&team.id).unwrap().unwrap();
+        assert_eq!(listed.origin, Origin::Shared);
+        assert!(store.put(&listed).is_err());
+        assert!(store.delete(&team.id).is_err());
+
+        listed.origin = Origin::User;
+        listed.code = "make -j8".into();
+        store.put(&listed).unwrap();
+        let forked = store.get(&team.id).unwrap().unwrap();
+        assert_eq!(forked.origin, Origin::User);
+        assert_eq!(forked.code, "make -j8");
+
+        store.delete(&team.id).unwrap();
+        assert_eq!(store.get(&team.id).unwrap().unwrap().origin, Origin::Shared);
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        listed.origin = Origin::Project;
+        assert!(store.put(&listed).is_err());
+        assert!(store.user.list().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn queries_go_through_the_user_index() {
+        use crate::store::sqlite::SqliteStore;
+
+        let dir = TempDir::new();
+        let mut user = SqliteStore::open(&dir.join("snippets.db"), &dir.join("snippets")).unwrap();
+        let mut deploy = Snippet::new("deploy".into(), vec![], "docker compose up".into());
+        let logs = Snippet::new("logs".into(), vec![], "docker logs -f".into());
+        user.put(&deploy).unwrap();
+        user.put(&logs).unwrap();
+        deploy.code = "make deploy".into();
+        let mut project = JsonDirStore::new(dir.join("project"));
+        project.put(&deploy).unwrap();
+        let team = Snippet::new("team".into(), vec![], "docker ps".into());
+        JsonDirStore::new(dir.join("shared")).put(&team).unwrap();
+
+        let store = LayeredStore::new(
+            Box::new(user),
+            Some(project),
+            vec![JsonDirStore::read_only(dir.join("shared"))],
+        );
+        assert!(store.has_index());
+        let mut hits: Vec<(String, Origin)> = store
+            .query("docker")
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.description, s.origin))
+            .collect();
+        hits.sort_by(|a, b| a.0.cmp(&b.0));
+        // The project's copy of deploy does not mention docker and hides the user's.
+        assert_eq!(
+            hits,
+            [
+                ("logs".into(), Origin::User),
+                ("team".into(), Origin::Shared)
+            ]
+        );
+        // The index only matches word prefixes, unlike the scan of the other layers.
+        let infix: Vec<Origin> = store
+            .query("ocker")
+            .unwrap()
+            .iter()
+            .map(|s| s.origin)
+            .collect();
+        assert_eq!(infix, [Origin::Shared]);
+    }
+}
@@ -0,0 +1,548 @@
+//! SQLite snippet backend (enabled with the `sqlite` cargo feature).
+//!
+//! Each snippet is a row in `snippets`, with the full JSON document kept in the
+//! `data` column so new `Snippet` fields don't need schema changes. An FTS5 table
+//! (`snippets_fts`) indexes description, tags and code and is kept in sync on every
+//! write, so [`SnippetStore::query`] never has to scan the whole library.
+//! Replaced versions are kept in the `revisions` table, numbered from 1 per
+//! snippet. Deleted snippets are moved to the `trash` table until they are
+//! restored or purged after `trash.retention_days`.
+
+use crate::migrate::{self, Upgrade};
+use crate::snippets::now_unix;
+use crate::snippets::Snippet;
+use crate::store::{trash_cutoff, JsonDirStore, Revision, SnippetStore, Trashed};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde_json::Value;
+use std::io;
+use std::path::Path;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS snippets (
+        id          TEXT PRIMARY KEY,
+        description TEXT NOT NULL,
+        tags        TEXT NOT NULL,
+        code        TEXT NOT NULL,
+        data        TEXT NOT NULL
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS snippets_fts USING fts5(
+        id UNINDEXED,
+        description,
+        tags,
+        code
+    );
+    CREATE TABLE IF NOT EXISTS revisions (
+        id     TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        data   TEXT NOT NULL,
+        PRIMARY KEY (id, number)
+    );
+    CREATE TABLE IF NOT EXISTS trash (
+        id         TEXT NOT NULL,
+        deleted_at INTEGER NOT NULL,
+        data       TEXT NOT NULL
+    );
+";
+
+/// `PRAGMA user_version` once the one-time JSON import has been committed.
+const IMPORTED: i64 = 1;
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+fn to_io(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `db_path`.
+    ///
+    /// Until the database records a completed import, every snippet found in
+    /// `json_dir` is imported so switching backends keeps the library. The JSON
+    /// files are left untouched.
+    pub fn open(db_path: &Path, json_dir: &Path) -> io::Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(db_path).map_err(to_io)?;
+        conn.execute_batch(SCHEMA).map_err(to_io)?;
+        let mut store = Self { conn };
+
+        let version: i64 = store
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(to_io)?;
+        if version < IMPORTED {
+            let imported = store.import(json_dir)?;
+            if imported > 0 {
+                eprintln!(
+                    "Imported {} snippets from {} into {}",
+                    imported,
+                    json_dir.display(),
+                    db_path.display()
+                );
+            }
+        }
+        Ok(store)
+    }
+
+    /// Insert every snippet of `json_dir` and record the import, in a single
+    /// transaction so an interrupted import is retried on the next open.
+    /// Databases that already hold snippets (from before the import was
+    /// recorded) are only marked as imported. Returns how many were inserted.
+    fn import(&mut self, json_dir: &Path) -> io::Result<usize> {
+        let tx = self.conn.transaction().map_err(to_io)?;
+        let empty = tx
+            .query_row("SELECT 1 FROM snippets LIMIT 1", [], |_| Ok(()))
+            .optional()
+            .map_err(to_io)?
+            .is_none();
+        let snippets = if empty && json_dir.is_dir() {
+            JsonDirStore::read_only(json_dir.to_path_buf()).list()?
+        } else {
+            Vec::new()
+        };
+        for snippet in &snippets {
+            write_row(&tx, snippet)?;
+        }
+        tx.pragma_update(None, "user_version", IMPORTED)
+            .map_err(to_io)?;
+        tx.commit().map_err(to_io)?;
+        Ok(snippets.len())
+    }
+}
+
+/// Ids of the rows in the database at `db_path` whose document cannot be
+/// parsed, with the reason. The database is opened read-only and nothing is
+/// imported; a missing database has no such rows.
+pub fn unreadable(db_path: &Path) -> io::Result<Vec<(String, io::Error)>> {
+    if !db_path.is_file() {
+        return Ok(Vec::new());
+    }
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(to_io)?;
+    let mut stmt = conn
+        .prepare("SELECT id, data FROM snippets")
+        .map_err(to_io)?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(to_io)?;
+    let mut bad = Vec::new();
+    for row in rows {
+        let (id, data): (String, String) = row.map_err(to_io)?;
+        if let Err(e) = parse_row(data) {
+            bad.push((id, e));
+        }
+    }
+    Ok(bad)
+}
+
+/// Replace the row and FTS entry for `snippet`.
+fn write_row(conn: &Connection, snippet: &Snippet) -> io::Result<()> {
+    let data = serde_json::to_string(snippet).map_err(io::Error::other)?;
+    let tags = snippet.tags.join(" ");
+    conn.execute(
+        "INSERT OR REPLACE INTO snippets (id, description, tags, code, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![snippet.id, snippet.description, tags, snippet.code, data],
+    )
+    .map_err(to_io)?;
+    conn.execute(
+        "DELETE FROM snippets_fts WHERE id = ?1",
+        params![snippet.id],
+    )
+    .map_err(to_io)?;
+    conn.execute(
+        "INSERT INTO snippets_fts (id, description, tags, code) VALUES (?1, ?2, ?3, ?4)",
+        params![snippet.id, snippet.description, tags, snippet.code],
+    )
+    .map_err(to_io)?;
+    Ok(())
+}
+
+/// Keep the stored version of `snippet` as its next revision if it differs.
+/// A row that is not a valid snippet is not kept.
+fn record_revision(conn: &Connection, snippet: &Snippet) -> io::Result<()> {
+    let current: Option<String> = conn
+        .query_row(
+            "SELECT data FROM snippets WHERE id = ?1",
+            params![snippet.id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(to_io)?;
+    let Some(current) = current else {
+        return Ok(());
+    };
+    let replacement = serde_json::to_string(snippet).map_err(io::Error::other)?;
+    if current == replacement || parse_row(current.clone()).is_err() {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO revisions (id, number, data) \
+         SELECT ?1, COALESCE(MAX(number), 0) + 1, ?2 FROM revisions WHERE id = ?1",
+        params![snippet.id, current],
+    )
+    .map_err(to_io)?;
+    Ok(())
+}
+
+/// Remove trashed rows deleted before `before` (all with `None`), along with
+/// the revisions of snippets that are now gone for good.
+fn purge_trash(conn: &Connection, before: Option<u64>) -> io::Result<usize> {
+    let purged = conn
+        .execute(
+            "DELETE FROM trash WHERE ?1 IS NULL OR deleted_at < ?1",
+            params![before.map(|before| before as i64)],
+        )
+        .map_err(to_io)?;
+    conn.execute(
+        "DELETE FROM revisions WHERE id NOT IN (SELECT id FROM snippets) \
+         AND id NOT IN (SELECT id FROM trash)",
+        [],
+    )
+    .map_err(to_io)?;
+    Ok(purged)
+}
+
+/// Deserialize the `(id, data)` rows of a query, skipping malformed ones with a warning.
+fn parse_rows(
+    rows: impl Iterator<Item = rusqlite::Result<(String, String)>>,
+) -> io::Result<Vec<Snippet>> {
+    let mut snippets = Vec::new();
+    for row in rows {
+        let (id, data) = row.map_err(to_io)?;
+        match parse_row(data) {
+            Ok(snippet) => snippets.push(snippet),
+            Err(e) => eprintln!("Failed to parse snippet {}: {}", id, e),
+        }
+    }
+    Ok(snippets)
+}
+
+/// Deserialize a stored document, upgrading older schema versions in memory.
+fn parse_row(data: String) -> io::Result<Snippet> {
+    let mut value: Value =
+        serde_json::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    migrate::upgrade(&mut value)?;
+    serde_json::from_value(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Turn free text into an FTS5 query: every whitespace-separated word becomes a
+/// quoted prefix term, so user input can never be a syntax error.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl SnippetStore for SqliteStore {
+    fn get(&self, id: &str) -> io::Result<Option<Snippet>> {
+        self.conn
+            .query_row(
+                "SELECT data FROM snippets WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(to_io)?
+            .map(parse_row)
+            .transpose()
+    }
+
+    /// Keeps the replaced version in `revisions`, in the same transaction.
+    fn put(&mut self, snippet: &Snippet) -> io::Result<()> {
+        let tx = self.conn.transaction().map_err(to_io)?;
+        record_revision(&tx, snippet)?;
+        write_row(&tx, snippet)?;
+        tx.commit().map_err(to_io)
+    }
+
+    /// Moves the row to the trash, then purges trashed snippets older than
+    /// `trash.retention_days`.
+    fn delete(&mut self, id: &str) -> io::Result<()> {
+        let tx = self.conn.transaction().map_err(to_io)?;
+        tx.execute(
+            "INSERT INTO trash (id, deleted_at, data) SELECT id, ?2, data FROM snippets WHERE id = ?1",
+            params![id, now_unix() as i64],
+        )
+        .map_err(to_io)?;
+        tx.execute("DELETE FROM snippets WHERE id = ?1", params![id])
+            .map_err(to_io)?;
+        tx.execute("DELETE FROM snippets_fts WHERE id = ?1", params![id])
+            .map_err(to_io)?;
+        if let Some(cutoff) = trash_cutoff() {
+            purge_trash(&tx, Some(cutoff))?;
+        }
+        tx.commit().map_err(to_io)
+    }
+
+    /// Malformed rows are skipped with a warning.
+    fn list(&self) -> io::Result<Vec<Snippet>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, data FROM snippets")
+            .map_err(to_io)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(to_io)?;
+        parse_rows(rows)
+    }
+
+    /// Full-text search, best matches first (BM25 ranking). Malformed rows are
+    /// skipped with a warning.
+    fn query(&self, query: &str) -> io::Result<Vec<Snippet>> {
+        let fts = fts_query(query);
+        if fts.is_empty() {
+            return self.list();
+        }
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT s.id, s.data FROM snippets_fts f JOIN snippets s ON s.id = f.id \
+                 WHERE snippets_fts MATCH ?1 ORDER BY bm25(snippets_fts)",
+            )
+            .map_err(to_io)?;
+        let rows = stmt
+            .query_map(params![fts], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(to_io)?;
+        parse_rows(rows)
+    }
+
+    /// Rewrite every row whose stored document uses an older schema version.
+    /// Malformed rows are skipped with a warning.
+    fn migrate(&mut self, dry_run: bool) -> io::Result<Vec<Upgrade>> {
+        let rows: Vec<(String, String)> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT id, data FROM snippets")
+                .map_err(to_io)?;
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(to_io)?
+                .collect::<Result<_, _>>()
+                .map_err(to_io)?
+        };
+
+        let tx = self.conn.transaction().map_err(to_io)?;
+        let mut upgrades = Vec::new();
+        for (id, data) in rows {
+            let mut value: Value = match serde_json::from_str(&data) {
+                Ok(value) => value,
+                Err(e) => {
+                    eprintln!("Failed to parse snippet {}: {}", id, e);
+                    continue;
+                }
+            };
+            match migrate::upgrade(&mut value) {
+                Ok(Some(from)) => {
+                    if !dry_run {
+                        match serde_json::from_value::<Snippet>(value) {
+                            Ok(snippet) => write_row(&tx, &snippet)?,
+                            Err(e) => {
+                                eprintln!("Skipping snippet {}: {}", id, e);
+                                continue;
+                            }
+                        }
+                    }
+                    upgrades.push(Upgrade { item: id, from });
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Skipping snippet {}: {}", id, e),
+            }
+        }
+        tx.commit().map_err(to_io)?;
+        Ok(upgrades)
+    }
+
+    /// Malformed revisions are skipped with a warning.
+    fn history(&self, id: &str) -> io::Result<Vec<Revision>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT number, data FROM revisions WHERE id = ?1 ORDER BY number")
+            .map_err(to_io)?;
+        let rows = stmt
+            .query_map(params![id], |row| {
+                Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(to_io)?;
+        let mut revisions = Vec::new();
+        for row in rows {
+            let (number, data) = row.map_err(to_io)?;
+            match parse_row(data) {
+                Ok(snippet) => revisions.push(Revision { number, snippet }),
+                Err(e) => eprintln!("Failed to parse revision {} of {}: {}", number, id, e),
+            }
+        }
+        Ok(revisions)
+    }
+
+    /// Malformed entries are skipped with a warning.
+    fn trash(&self) -> io::Result<Vec<Trashed>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, deleted_at, data FROM trash ORDER BY deleted_at DESC, rowid DESC")
+            .map_err(to_io)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(to_io)?;
+        let mut trashed = Vec::new();
+        for row in rows {
+            let (id, deleted_at, data) = row.map_err(to_io)?;
+            match parse_row(data) {
+                Ok(snippet) => trashed.push(Trashed {
+                    snippet,
+                    deleted_at: deleted_at as u64,
+                }),
+                Err(e) => eprintln!("Failed to parse trashed snippet {}: {}", id, e),
+            }
+        }
+        Ok(trashed)
+    }
+
+    fn restore_trashed(&mut self, id: &str) -> io::Result<Snippet> {
+        let tx = self.conn.transaction().map_err(to_io)?;
+        let Some((rowid, data)) = tx
+            .query_row(
+                "SELECT rowid, data FROM trash WHERE id = ?1 ORDER BY deleted_at DESC, rowid DESC",
+                params![id],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()
+            .map_err(to_io)?
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("snippet {} is not in the trash", id),
+            ));
+        };
+        let exists = tx
+            .query_row("SELECT 1 FROM snippets WHERE id = ?1", params![id], |_| {
+                Ok(())
+            })
+            .optional()
+            .map_err(to_io)?
+            .is_some();
+        if exists {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("snippet {} exists again; delete it before restoring", id),
+            ));
+        }
+        let snippet = parse_row(data)?;
+        write_row(&tx, &snippet)?;
+        tx.execute("DELETE FROM trash WHERE rowid = ?1", params![rowid])
+            .map_err(to_io)?;
+        tx.commit().map_err(to_io)?;
+        Ok(snippet)
+    }
+
+    /// Purges the trash and the orphaned revisions in one transaction.
+    fn empty_trash(&mut self, before: Option<u64>) -> io::Result<usize> {
+        let tx = self.conn.transaction().map_err(to_io)?;
+        let purged = purge_trash(&tx, before)?;
+        tx.commit().map_err(to_io)?;
+        Ok(purged)
+    }
+
+    fn has_index(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsutil::TempDir;
+
+    #[test]
+    fn imports_json_and_searches() {
+        let root = TempDir::new();
+        let json_dir = root.join("snippets");
+        let mut json = JsonDirStore::new(json_dir.clone());
+        let docker = Snippet::new(
+            "Run container".into(),
+            vec!["docker".into()],
+            "docker run -it ubuntu".into(),
+        );
+        json.put(&docker).unwrap();
+
+        let mut store = SqliteStore::open(&root.join("snippets.db"), &json_dir).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(store.query("dock").unwrap()[0].id, docker.id);
+        assert!(store.query("kubectl").unwrap().is_empty());
+        store.put(&docker).unwrap();
+        assert!(store.history(&docker.id).unwrap().is_empty());
+        let mut edited = docker.clone();
+        edited.code = "docker run --rm -it ubuntu".into();
+        store.put(&edited).unwrap();
+        edited.code = "docker run --rm -it debian".into();
+        store.put(&edited).unwrap();
+        let history = store.history(&docker.id).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].number, 1);
+        assert_eq!(history[0].snippet.code, "docker run -it ubuntu");
+        assert_eq!(history[1].snippet.code, "docker run --rm -it ubuntu");
+        store.put(&docker).unwrap();
+        assert_eq!(store.query("say \"hi").unwrap().len(), 0);
+        let fuzzy = crate::search::candidates(&store, "dockr").unwrap();
+        assert_eq!(crate::search::rank(fuzzy, "dockr")[0].snippet.id, docker.id);
+
+        store.delete(&docker.id).unwrap();
+        assert!(store.get(&docker.id).unwrap().is_none());
+        assert!(store.query("docker").unwrap().is_empty());
+        assert_eq!(store.trash().unwrap()[0].snippet.id, docker.id);
+
+        assert_eq!(store.restore_trashed(&docker.id).unwrap().id, docker.id);
+        assert_eq!(store.query("docker").unwrap()[0].id, docker.id);
+        assert!(store.trash().unwrap().is_empty());
+        let err = store.restore_trashed(&docker.id).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        store.delete(&docker.id).unwrap();
+        assert_eq!(store.empty_trash(None).unwrap(), 1);
+        assert!(store.trash().unwrap().is_empty());
+        assert!(store.history(&docker.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn retries_an_interrupted_import_and_skips_malformed_rows() {
+        let root = TempDir::new();
+        let json_dir = root.join("snippets");
+        let snippet = Snippet::new("Greet".into(), vec![], "echo hi".into());
+        JsonDirStore::new(json_dir.clone()).put(&snippet).unwrap();
+        let db_path = root.join("snippets.db");
+        // A process killed after creating the database, before importing.
+        Connection::open(&db_path)
+            .unwrap()
+            .execute_batch(SCHEMA)
+            .unwrap();
+
+        let mut store = SqliteStore::open(&db_path, &json_dir).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        store.delete(&snippet.id).unwrap();
+        let mut store = SqliteStore::open(&db_path, &json_dir).unwrap();
+        assert!(store.list().unwrap().is_empty());
+
+        store
+            .conn
+            .execute(
+                "INSERT INTO snippets (id, description, tags, code, data) VALUES ('bad', 'bad', '', '', '{')",
+                [],
+            )
+            .unwrap();
+        store.restore_trashed(&snippet.id).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        let bad = unreadable(&db_path).unwrap();
+        assert_eq!(bad.len(), 1);
+        assert_eq!(bad[0].0, "bad");
+        assert!(store.migrate(false).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+//! Placeholder variables in snippet bodies.
+//!
+//! Syntax:
+//! - `{{name}}`: required value
+//! - `{{name:default}}`: value with a default
+//! - `{{name:a|b|c}}`: one of a fixed set of choices; the first is the default
+//!
+//! Names may contain letters, digits, `_` and `-` and must follow the braces
+//! directly. Anything else between double braces (e.g. `{{ name }}` in a Jinja
+//! template or `{{ .Values.image }}` in a Helm chart) is left untouched, and
+//! `\{{` stands for literal braces: `\{{name}}` renders as `{{name}}`.
+
+use std::collections::HashMap;
+use std::io;
+
+/// A placeholder found in a snippet body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    pub name: String,
+    /// Value used when none is supplied.
+    pub default: Option<String>,
+    /// Allowed values; empty means any value is accepted.
+    pub choices: Vec<String>,
+}
+
+impl Placeholder {
+    /// Label for prompts, e.g. `env (dev|prod)`.
+    pub fn label(&self) -> String {
+        if self.choices.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} ({})", self.name, self.choices.join("|"))
+        }
+    }
+}
+
+/// One `{{...}}` placeholder or `\{{` escape: byte range in the source and
+/// the parsed placeholder (`None` for an escape).
+struct Occurrence {
+    start: usize,
+    end: usize,
+    placeholder: Option<Placeholder>,
+}
+
+fn parse_inner(inner: &str) -> Option<Placeholder> {
+    let (name, spec) = match inner.split_once(':') {
+        Some((name, spec)) => (name, Some(spec.trim())),
+        None => (inner, None),
+    };
+    let valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_name {
+        return None;
+    }
+    let (default, choices) = match spec {
+        Some(spec) if spec.contains('|') => {
+            let choices: Vec<String> = spec.split('|').map(|c| c.trim().to_string()).collect();
+            (choices.first().cloned(), choices)
+        }
+        Some(spec) => (Some(spec.to_string()), Vec::new()),
+        None => (None, Vec::new()),
+    };
+    Some(Placeholder {
+        name: name.to_string(),
+        default,
+        choices,
+    })
+}
+
+fn occurrences(code: &str) -> Vec<Occurrence> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(open) = code[pos..].find("{{") {
+        let start = pos + open;
+        if code[..start].ends_with('\\') {
+            found.push(Occurrence {
+                start: start - 1,
+                end: start + 2,
+                placeholder: None,
+            });
+            pos = start + 2;
+            continue;
+        }
+        let Some(close) = code[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+        match parse_inner(&code[start + 2..end - 2]) {
+            Some(placeholder) => {
+                found.push(Occurrence {
+                    start,
+                    end,
+                    placeholder: Some(placeholder),
+                });
+                pos = end;
+            }
+            None => pos = start + 2,
+        }
+    }
+    found
+}
+
+/// Distinct placeholders in `code`, in order of first appearance.
+///
+/// If a name appears several times, the first occurrence that specifies a
+/// default or choices wins.
+pub fn placeholders(code: &str) -> Vec<Placeholder> {
+    let mut result: Vec<Placeholder> = Vec::new();
+    for placeholder in occurrences(code).into_iter().filter_map(|o| o.placeholder) {
+        match result.iter_mut().find(|p| p.name == placeholder.name) {
+            Some(existing) => {
+                if existing.default.is_none() && existing.choices.is_empty() {
+                    *existing = placeholder;
+                }
+            }
+            None => result.push(placeholder),
+        }
+    }
+    result
+}
+
+/// Substitute every placeholder in `code` with its value from `values`, falling
+/// back to its default if it has none. An empty value is a value.
+///
+/// # Errors
+/// Returns `InvalidInput` listing every placeholder without a value or default,
+/// or naming a value that is not one of the placeholder's choices.
+pub fn render(code: &str, values: &HashMap<String, String>) -> io::Result<String> {
+    let mut resolved: HashMap<String, String> = HashMap::new();
+    let mut missing = Vec::new();
+    for p in placeholders(code) {
+        let value = values.get(&p.name).or(p.default.as_ref());
+        match value {
+            Some(v) if !p.choices.is_empty() && !p.choices.contains(v) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "invalid value '{}' for {} (choices: {})",
+                        v,
+                        p.name,
+                        p.choices.join(", ")
+                    ),
+                ));
+            }
+            Some(v) => {
+                resolved.insert(p.name, v.clone());
+            }
+            None => missing.push(p.name),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unfilled placeholders: {}", missing.join(", ")),
+        ));
+    }
+
+    let mut out = String::with_capacity(code.len());
+    let mut last = 0;
+    for occ in occurrences(code) {
+        out.push_str(&code[last..occ.start]);
+        match &occ.placeholder {
+            Some(placeholder) => out.push_str(&resolved[&placeholder.name]),
+            None => out.push_str("{{"),
+        }
+        last = occ.end;
+    }
+    out.push_str(&code[last..]);
+    Ok(out)
+}
+
+/// Parse a `name=value` command-line assignment.
+pub fn parse_var(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.trim().to_string(), v.to_string()))
+        .filter(|(k, _)| !k.is_empty())
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_defaults_and_choices() {
+        let code = "docker run -p {{port:8080}}:80 {{image}} --env {{env:dev|prod}} {{ .Values.x }} {{port}}";
+        let found = placeholders(code);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].default.as_deref(), Some("8080"));
+        assert_eq!(found[1].name, "image");
+        assert_eq!(found[2].choices, ["dev", "prod"]);
+        assert_eq!(found[2].label(), "env (dev|prod)");
+    }
+
+    #[test]
+    fn renders_values_and_reports_missing() {
+        let code = "run -p {{port:8080}}:{{port}} {{image}} {{ .Keep }}";
+        let mut values = HashMap::new();
+        let err = render(code, &values).unwrap_err();
+        assert!(err.to_string().contains("image"));
+
+        values.insert("image".to_string(), "nginx".to_string());
+        assert_eq!(
+            render(code, &values).unwrap(),
+            "run -p 8080:8080 nginx {{ .Keep }}"
+        );
+    }
+
+    #[test]
+    fn leaves_template_syntax_and_escapes_alone() {
+        let code = "Hello {{ name }}, {{ .Values.x }}, ${{ github.sha }} \\{{user}} {{user}}";
+        assert_eq!(placeholders(code).len(), 1);
+        let values = HashMap::from([("user".to_string(), String::new())]);
+        assert_eq!(
+            render(code, &values).unwrap(),
+            "Hello {{ name }}, {{ .Values.x }}, ${{ github.sha }} {{user}} "
+        );
+        assert!(render("{{user}}", &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn rejects_values_outside_choices() {
+        let mut values = HashMap::new();
+        values.insert("env".to_string(), "qa".to_string());
+        assert!(render("{{env:dev|prod}}", &values).is_err());
+        assert_eq!(parse_var("a=b=c").unwrap(), ("a".into(), "b=c".into()));
+        assert!(parse_var("novalue").is_err());
+    }
+}
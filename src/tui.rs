@@ -1,39 +1,132 @@
 //! Terminal user interface (TUI) for interactive snippet search and copy.
 //!
+//! With [`Output::Stdout`] the TUI draws on the controlling terminal instead of
+//! stdout, so the chosen code can be captured with `$(snipman interactive --print)`.
+//!
 //! Key bindings:
 //! - Type to filter by description (fuzzy)
 //! - Up/Down to navigate
-//! - Enter to copy selected snippet to clipboard and exit
-//! - q to quit without copying
-//! - p: preview selected snippet code
-//! - d: delete selected snippet
+//! - Enter to copy selected snippet to clipboard and exit; if the body has
+//!   `{{placeholders}}`, a form asks for their values first
+//! - Ctrl-Y: copy the selected snippet as written, placeholders and all
+//! - Esc or Ctrl-Q to quit without copying (Esc always quits; quit, delete
+//!   and preview were the plain keys q, d and p before every action moved to Ctrl)
+//! - Ctrl-P: preview selected snippet code
+//! - Ctrl-D: delete selected snippet (it goes to the trash)
+//! - Ctrl-U: undo the last deletion of this session
 //! - PgUp/PgDn: scroll preview up/down
+//! - Left/Right: page the preview through earlier revisions of the selected snippet
+//! - Tab: cycle sort order (description, created, updated, language) while the query is empty
+//! - Ctrl-E: edit the selected snippet's body in $VISUAL/$EDITOR
+//! - Ctrl-R: edit the selected snippet's description and tags in a form
+//! - Ctrl-X: run the selected snippet in your shell (after filling placeholders and confirming)
+//! - Ctrl-A: add a new snippet (form for description, tags and language, then the editor for the body)
+//! - Ctrl-F: fork the selected shared snippet into your library so it can be changed
+//! - Ctrl-L: switch to another snippet library
+//!
+//! Snippets from the current project's `.snipman/` directory are marked `[project]`,
+//! read-only ones from shared libraries with a lock.
+//!
+//! The keys, colors and compact preview size can be changed in the `[tui]`
+//! section of the config file.
+
+mod form;
 
-use crate::snippets::{delete_snippet, Snippet};
-use arboard::Clipboard;
+use crate::config;
+use crate::editor::open_editor_with;
+use crate::library;
+use crate::os::OsKind;
+use crate::runner;
+use crate::search;
+use crate::snippets::{self, Origin, Snippet, SortKey};
+use crate::store::{self, Revision, SnippetStore};
+use crate::template;
+use clap::ValueEnum;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use form::{Form, FormEvent};
 use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
 use ratatui::{
-    backend::CrosstermBackend, layout::{Constraint, Direction, Layout},
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
-    Frame,
-    Terminal,
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
 };
-use std::io;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::process::{ExitStatus, Stdio};
+
+/// Where the chosen snippet is delivered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Copy it to the clipboard; the TUI is drawn on stdout.
+    #[default]
+    Clipboard,
+    /// Print it to stdout; the TUI is drawn on the terminal device.
+    Stdout,
+}
+
+/// What submitting the open form does.
+enum FormAction {
+    /// Update description and tags of the snippet with this id.
+    EditDetails { id: String },
+    /// Create a snippet from description, tags and language; the body comes from the editor.
+    NewSnippet,
+    /// Substitute the form's values (one field per name, in order) into `code`, then `pick` it.
+    FillVars {
+        code: String,
+        names: Vec<String>,
+        pick: Pick,
+    },
+}
+
+/// What to do with a chosen snippet once its placeholders are filled.
+#[derive(Clone, Copy)]
+enum Pick {
+    /// Exit and copy it to the clipboard.
+    Copy,
+    /// Ask for confirmation, then run it in the shell.
+    Run,
+}
 
 enum Mode {
     Normal,
     ConfirmDelete,
+    ConfirmRun {
+        command: String,
+    },
+    Form(Form, FormAction),
+    PickLibrary {
+        names: Vec<String>,
+        state: ListState,
+    },
+}
+
+/// Why the event loop returned.
+enum Exit {
+    /// The user picked this text, or quit.
+    Done(Option<String>),
+    /// Reopen the TUI on another library.
+    Switch(String),
+}
+
+/// Earlier versions of one snippet, paged through in the preview.
+struct HistoryView {
+    revisions: Vec<Revision>,
+    /// Index into `revisions` of the version shown.
+    shown: usize,
 }
 
 /// In-memory state for the interactive app.
-struct App {
+struct App<'a> {
+    store: &'a mut dyn SnippetStore,
     all_snippets: Vec<Snippet>,
     visible_snippets: Vec<usize>,
     list_state: ListState,
@@ -42,55 +135,75 @@ struct App {
     mode: Mode,
     preview_full: bool,
     preview_scroll: u16,
+    /// Set while the preview shows an earlier revision of the selected snippet.
+    history: Option<HistoryView>,
     status_msg: Option<String>,
+    sort: SortKey,
+    /// Final text picked by the user; set when the TUI should exit and copy it.
+    chosen: Option<String>,
+    /// Name of the library `store` belongs to.
+    library: String,
+    /// Library picked in the switcher; set when the TUI should reopen on it.
+    switch_to: Option<String>,
+    /// Ids deleted in this session, most recent last, for undo.
+    deleted: Vec<String>,
 }
 
-impl App {
-    fn new(snippets: Vec<Snippet>) -> App {
-        let visible_indices = (0..snippets.len()).collect();
-        App {
+impl<'a> App<'a> {
+    fn new(store: &'a mut dyn SnippetStore) -> io::Result<App<'a>> {
+        let snippets = store.list()?;
+        let mut app = App {
+            store,
             all_snippets: snippets,
-            visible_snippets: visible_indices,
+            visible_snippets: Vec::new(),
             list_state: ListState::default(),
             search_query: String::new(),
             matcher: SkimMatcherV2::default(),
             mode: Mode::Normal,
             preview_full: false,
             preview_scroll: 0,
+            history: None,
             status_msg: None,
-        }
+            sort: config::get().default_sort,
+            chosen: None,
+            library: library::active().to_string(),
+            switch_to: None,
+            deleted: Vec::new(),
+        };
+        app.filter_snippets();
+        Ok(app)
     }
 
     fn filter_snippets(&mut self) {
         if self.search_query.is_empty() {
-            self.visible_snippets = (0..self.all_snippets.len()).collect();
+            let mut indices: Vec<usize> = (0..self.all_snippets.len()).collect();
+            indices.sort_by(|&a, &b| {
+                self.sort
+                    .compare(&self.all_snippets[a], &self.all_snippets[b])
+            });
+            self.visible_snippets = indices;
+        } else if self.store.has_index() {
+            self.visible_snippets = match self.store.query(&self.search_query) {
+                // The index only matches words and prefixes; typos need fuzzy matching.
+                Ok(hits) if hits.is_empty() => self.fuzzy_rank(),
+                Ok(hits) => {
+                    let positions: HashMap<&str, usize> = self
+                        .all_snippets
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, s)| (s.id.as_str(), idx))
+                        .collect();
+                    hits.iter()
+                        .filter_map(|s| positions.get(s.id.as_str()).copied())
+                        .collect()
+                }
+                Err(e) => {
+                    self.status_msg = Some(format!("Search failed: {}", e));
+                    Vec::new()
+                }
+            };
         } else {
-            let query = self.search_query.as_str();
-            let matcher = &self.matcher;
-
-            let mut scored: Vec<(usize, i64)> = self
-                .all_snippets
-                .iter()
-                .enumerate()
-                .filter_map(|(idx, snippet)| {
-                    let mut best: Option<i64> = None;
-
-                    if let Some(s) = matcher.fuzzy_match(&snippet.description, query) {
-                        best = Some(s);
-                    }
-                    if let Some(s) = matcher.fuzzy_match(&snippet.tags.join(" "), query) {
-                        best = Some(best.map_or(s, |b| b.max(s)));
-                    }
-                    if let Some(s) = matcher.fuzzy_match(&snippet.code, query) {
-                        best = Some(best.map_or(s, |b| b.max(s)));
-                    }
-
-                    best.map(|score| (idx, score))
-                })
-                .collect();
-
-            scored.sort_by(|a, b| b.1.cmp(&a.1));
-            self.visible_snippets = scored.into_iter().map(|(idx, _)| idx).collect();
+            self.visible_snippets = self.fuzzy_rank();
         }
 
         if !self.visible_snippets.is_empty() {
@@ -99,14 +212,31 @@ impl App {
             self.list_state.select(None);
         }
         self.preview_scroll = 0;
+        self.history = None;
+    }
+
+    /// Indices of `all_snippets` that fuzzy-match the query, best match first.
+    fn fuzzy_rank(&self) -> Vec<usize> {
+        let query = self.search_query.as_str();
+        let matcher = &self.matcher;
+
+        let mut scored: Vec<(usize, i64)> = self
+            .all_snippets
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, snippet)| {
+                search::score(matcher, snippet, query).map(|score| (idx, score))
+            })
+            .collect();
+
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        scored.into_iter().map(|(idx, _)| idx).collect()
     }
 
     fn next(&mut self) {
         let i = match self.list_state.selected() {
             Some(i) => {
-                if self.visible_snippets.is_empty() {
-                    0
-                } else if i >= self.visible_snippets.len() - 1 {
+                if self.visible_snippets.is_empty() || i >= self.visible_snippets.len() - 1 {
                     0
                 } else {
                     i + 1
@@ -116,6 +246,7 @@ impl App {
         };
         self.list_state.select(Some(i));
         self.preview_scroll = 0;
+        self.history = None;
     }
 
     fn previous(&mut self) {
@@ -133,6 +264,7 @@ impl App {
         };
         self.list_state.select(Some(i));
         self.preview_scroll = 0;
+        self.history = None;
     }
 
     fn selected_snippet(&self) -> Option<&Snippet> {
@@ -141,51 +273,537 @@ impl App {
             .and_then(|i| self.visible_snippets.get(i))
             .and_then(|&idx| self.all_snippets.get(idx))
     }
+
+    /// The version of the selected snippet shown in the preview.
+    fn previewed_snippet(&self) -> Option<&Snippet> {
+        match &self.history {
+            Some(view) => Some(&view.revisions[view.shown].snippet),
+            None => self.selected_snippet(),
+        }
+    }
+
+    /// Show the next older (or newer) version of the selected snippet in the
+    /// preview. Paging past the newest revision goes back to the current version.
+    fn page_history(&mut self, older: bool) {
+        self.preview_scroll = 0;
+        if let Some(view) = &mut self.history {
+            if !older {
+                view.shown += 1;
+                if view.shown == view.revisions.len() {
+                    self.history = None;
+                }
+            } else if view.shown > 0 {
+                view.shown -= 1;
+            } else {
+                self.status_msg = Some("Oldest revision.".to_string());
+            }
+            return;
+        }
+        if !older {
+            return;
+        }
+        let Some(id) = self.selected_snippet().map(|s| s.id.clone()) else {
+            return;
+        };
+        match self.store.history(&id) {
+            Ok(revisions) if revisions.is_empty() => {
+                self.status_msg = Some("No earlier revisions.".to_string());
+            }
+            Ok(revisions) => {
+                self.history = Some(HistoryView {
+                    shown: revisions.len() - 1,
+                    revisions,
+                });
+            }
+            Err(e) => self.status_msg = Some(format!("No history: {}", e)),
+        }
+    }
+
+    /// Delete the selected snippet from the store and the visible list,
+    /// keeping the selection at the same position where possible.
+    fn delete_selected(&mut self) -> io::Result<()> {
+        let Some(sel) = self.list_state.selected() else {
+            return Ok(());
+        };
+        let Some(&idx) = self.visible_snippets.get(sel) else {
+            return Ok(());
+        };
+        let id = self.all_snippets[idx].id.clone();
+        self.store.delete(&id)?;
+        self.all_snippets.retain(|s| s.id != id);
+        self.deleted.push(id);
+        self.filter_snippets();
+
+        if self.visible_snippets.is_empty() {
+            self.list_state.select(None);
+        } else {
+            let new_sel = sel.min(self.visible_snippets.len() - 1);
+            self.list_state.select(Some(new_sel));
+        }
+        Ok(())
+    }
+
+    /// Bring the snippet deleted last in this session back from the trash and
+    /// select it. Returns a status message.
+    fn undo_delete(&mut self) -> io::Result<String> {
+        let Some(id) = self.deleted.pop() else {
+            return Ok("Nothing to undo.".to_string());
+        };
+        let snippet = match self.store.restore_trashed(&id) {
+            Ok(snippet) => snippet,
+            Err(e) => {
+                self.deleted.push(id);
+                return Err(e);
+            }
+        };
+        let msg = format!("Restored '{}'.", snippet.description);
+        self.all_snippets.push(snippet);
+        self.filter_snippets();
+        self.select_id(&id);
+        Ok(msg)
+    }
+
+    /// Persist a changed snippet, update the in-memory list and keep it selected.
+    fn save(&mut self, mut snippet: Snippet) -> io::Result<()> {
+        snippet.touch();
+        self.store.put(&snippet)?;
+        let id = snippet.id.clone();
+        match self.all_snippets.iter_mut().find(|s| s.id == id) {
+            Some(existing) => *existing = snippet,
+            None => self.all_snippets.push(snippet),
+        }
+        self.filter_snippets();
+        self.select_id(&id);
+        Ok(())
+    }
+
+    /// Move the selection to the visible snippet with `id`, if any.
+    fn select_id(&mut self, id: &str) {
+        if let Some(pos) = self
+            .visible_snippets
+            .iter()
+            .position(|&idx| self.all_snippets[idx].id == id)
+        {
+            self.list_state.select(Some(pos));
+        }
+    }
+
+    /// Whether the selected snippet is read-only; if so, say how to change it.
+    fn selected_is_read_only(&mut self) -> bool {
+        let read_only = self
+            .selected_snippet()
+            .is_some_and(|s| s.origin == Origin::Shared);
+        if read_only {
+            self.status_msg = Some(format!(
+                "Shared snippets are read-only; press {} to fork it to your library",
+                chord(config::get().tui.keys.fork)
+            ));
+        }
+        read_only
+    }
+
+    /// Copy the selected shared snippet into the user's library, keeping its id
+    /// so the copy overrides the shared one.
+    fn fork_selected(&mut self) -> io::Result<String> {
+        let Some(mut snippet) = self.selected_snippet().cloned() else {
+            return Ok("No snippet selected.".to_string());
+        };
+        if snippet.origin != Origin::Shared {
+            return Ok("Only shared snippets need forking.".to_string());
+        }
+        snippet.origin = Origin::User;
+        self.save(snippet)?;
+        Ok("Forked into your library.".to_string())
+    }
+
+    /// Ask whether to delete the selected snippet.
+    fn confirm_delete(&mut self) {
+        if !self.selected_is_read_only() {
+            self.mode = Mode::ConfirmDelete;
+            self.status_msg = Some("Confirm delete? press 'y' or 'n'".to_string());
+        }
+    }
+
+    /// Open the description/tags form for the selected snippet.
+    fn open_details_form(&mut self) {
+        if self.selected_is_read_only() {
+            return;
+        }
+        if let Some(s) = self.selected_snippet() {
+            let form = Form::new(
+                "Edit details",
+                vec![
+                    ("Description", s.description.clone()),
+                    ("Tags", s.tags.join(", ")),
+                ],
+            );
+            self.mode = Mode::Form(form, FormAction::EditDetails { id: s.id.clone() });
+        }
+    }
+
+    /// Choose the selected snippet, asking for placeholder values first if it has any.
+    fn choose_selected(&mut self, pick: Pick) {
+        let Some(code) = self.selected_snippet().map(|s| s.code.clone()) else {
+            return;
+        };
+        let placeholders = template::placeholders(&code);
+        if placeholders.is_empty() {
+            self.deliver(code, pick);
+            return;
+        }
+        let form = Form::new(
+            "Fill placeholders",
+            placeholders
+                .iter()
+                .map(|p| (p.label(), p.default.clone().unwrap_or_default()))
+                .collect(),
+        );
+        let names = placeholders.into_iter().map(|p| p.name).collect();
+        self.mode = Mode::Form(form, FormAction::FillVars { code, names, pick });
+    }
+
+    /// Copy the selected snippet as written, leaving its placeholders unfilled.
+    fn copy_selected_raw(&mut self) {
+        if let Some(code) = self.selected_snippet().map(|s| s.code.clone()) {
+            self.chosen = Some(code);
+        }
+    }
+
+    /// Hand over fully substituted snippet text.
+    fn deliver(&mut self, text: String, pick: Pick) {
+        match pick {
+            Pick::Copy => self.chosen = Some(text),
+            Pick::Run => {
+                self.mode = Mode::ConfirmRun { command: text };
+                self.status_msg = Some("Run this command? press 'y' or 'n'".to_string());
+            }
+        }
+    }
+
+    /// Open the library switcher with the current library selected.
+    fn open_library_picker(&mut self) {
+        match library::list() {
+            Ok(names) => {
+                let current = names.iter().position(|n| *n == self.library);
+                let state = ListState::default().with_selected(current.or(Some(0)));
+                self.mode = Mode::PickLibrary { names, state };
+            }
+            Err(e) => self.status_msg = Some(format!("Cannot list libraries: {}", e)),
+        }
+    }
+
+    /// Open the form for a new snippet.
+    fn open_new_form(&mut self) {
+        let form = Form::new(
+            "New snippet",
+            vec![
+                ("Description", String::new()),
+                ("Tags", String::new()),
+                ("Language", String::new()),
+            ],
+        );
+        self.mode = Mode::Form(form, FormAction::NewSnippet);
+    }
+
+    /// Apply a submitted form and return a status message.
+    ///
+    /// `edit_body` is called with the initial text when a snippet body must be
+    /// written in the external editor.
+    fn submit_form(
+        &mut self,
+        form: Form,
+        action: FormAction,
+        edit_body: &mut dyn FnMut(&str) -> io::Result<String>,
+    ) -> io::Result<String> {
+        match action {
+            FormAction::EditDetails { id } => {
+                let description = required_description(&form)?;
+                let Some(mut snippet) = self.all_snippets.iter().find(|s| s.id == id).cloned()
+                else {
+                    return Ok("Snippet no longer exists.".to_string());
+                };
+                snippet.description = description.to_string();
+                snippet.tags = parse_tags(form.value(1));
+                self.save(snippet)?;
+                Ok("Updated snippet.".to_string())
+            }
+            FormAction::NewSnippet => {
+                let description = required_description(&form)?;
+                let code = edit_body("")?;
+                if code.trim().is_empty() {
+                    return Ok("Empty body; snippet not saved.".to_string());
+                }
+                let mut snippet =
+                    Snippet::new(description.to_string(), parse_tags(form.value(1)), code);
+                let language = form.value(2).trim();
+                if !language.is_empty() {
+                    snippet.language = Some(language.to_string());
+                }
+                self.save(snippet)?;
+                Ok("Added snippet.".to_string())
+            }
+            FormAction::FillVars { code, names, pick } => {
+                // An emptied field falls back to the default, like the CLI prompt.
+                let values = names
+                    .iter()
+                    .cloned()
+                    .zip(form.fields.iter().map(|f| f.value.clone()))
+                    .filter(|(_, value)| !value.is_empty())
+                    .collect();
+                let text = match template::render(&code, &values) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        // Keep the form open so the value can be fixed.
+                        self.mode = Mode::Form(form, FormAction::FillVars { code, names, pick });
+                        return Ok(format!("Cannot fill: {}", e));
+                    }
+                };
+                self.deliver(text, pick);
+                Ok(match pick {
+                    Pick::Copy => "Filled placeholders.".to_string(),
+                    Pick::Run => "Run this command? press 'y' or 'n'".to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// The trimmed first form field, which must not be empty.
+fn required_description(form: &Form) -> io::Result<&str> {
+    let description = form.value(0).trim();
+    if description.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "description cannot be empty",
+        ));
+    }
+    Ok(description)
+}
+
+/// Split a comma-separated tag list, dropping empty entries.
+fn parse_tags(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// The terminal the TUI is drawn on.
+struct Screen {
+    terminal: Terminal<CrosstermBackend<Box<dyn Write>>>,
+    /// The terminal device, when drawing there instead of on stdout.
+    tty: Option<File>,
+}
+
+impl Screen {
+    /// Enter raw mode and the alternate screen on stdout, or on the terminal
+    /// device for [`Output::Stdout`].
+    fn open(output: Output) -> io::Result<Self> {
+        let tty = match output {
+            Output::Clipboard => None,
+            Output::Stdout => {
+                let path = OsKind::current().tty_path();
+                Some(OpenOptions::new().read(true).write(true).open(path)?)
+            }
+        };
+        let mut writer: Box<dyn Write> = match &tty {
+            Some(tty) => Box::new(tty.try_clone()?),
+            None => Box::new(io::stdout()),
+        };
+        enable_raw_mode()?;
+        execute!(writer, EnterAlternateScreen, EnableMouseCapture)?;
+        Ok(Self {
+            terminal: Terminal::new(CrosstermBackend::new(writer))?,
+            tty,
+        })
+    }
+
+    /// Stdout for child processes (editor, commands) so they show up on the screen.
+    fn child_stdout(&self) -> io::Result<Stdio> {
+        match &self.tty {
+            Some(tty) => Ok(tty.try_clone()?.into()),
+            None => Ok(Stdio::inherit()),
+        }
+    }
+
+    /// Run `f` with the terminal restored to normal (cooked mode, main screen),
+    /// then switch back to the TUI.
+    fn suspended<T>(&mut self, f: impl FnOnce(&mut Self) -> io::Result<T>) -> io::Result<T> {
+        disable_raw_mode()?;
+        execute!(
+            self.terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )?;
+        let result = f(self);
+        enable_raw_mode()?;
+        execute!(
+            self.terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableMouseCapture
+        )?;
+        self.terminal.clear()?;
+        result
+    }
+
+    /// Leave raw mode and the alternate screen for good.
+    fn close(mut self) -> io::Result<()> {
+        disable_raw_mode()?;
+        execute!(
+            self.terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )?;
+        self.terminal.show_cursor()
+    }
 }
 
-/// Run the TUI and return the selected snippet's code if Enter is pressed.
-/// Returns Ok(None) if the user quits without selecting.
-pub fn run_tui(all_snippets: Vec<Snippet>) -> io::Result<Option<String>> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+/// Leave the TUI, let the user edit `initial` in their editor, and restore the TUI.
+fn edit_externally(screen: &mut Screen, initial: &str) -> io::Result<String> {
+    screen.suspended(|s| open_editor_with(initial, s.child_stdout()?))
+}
 
-    let mut app = App::new(all_snippets);
-    app.list_state.select(Some(0));
+/// Leave the TUI, run `command` in the shell on the normal screen, wait for
+/// Enter so the output can be read, and restore the TUI.
+fn run_externally(screen: &mut Screen, command: &str) -> io::Result<ExitStatus> {
+    screen.suspended(|s| {
+        let out = s.terminal.backend_mut();
+        writeln!(out, "$ {}", command)?;
+        out.flush()?;
+        let status = runner::run_with(command, s.child_stdout()?)?;
+        let out = s.terminal.backend_mut();
+        writeln!(
+            out,
+            "\n[snipman] Command {}. Press Enter to return.",
+            runner::describe(status)
+        )?;
+        out.flush()?;
+        loop {
+            if let Event::Key(key) = event::read()?
+                && key.code == KeyCode::Enter
+            {
+                return Ok(status);
+            }
+        }
+    })
+}
+
+/// Run the TUI on `store`, which belongs to `library`, and return the selected
+/// snippet's code if Enter is pressed. Returns Ok(None) if the user quits without
+/// selecting.
+///
+/// Delivering the code (clipboard or stdout) is up to the caller; `output` only
+/// decides where the TUI itself is drawn.
+pub fn run_tui(
+    mut store: Box<dyn SnippetStore>,
+    library: &str,
+    output: Output,
+) -> io::Result<Option<String>> {
+    let mut library = library.to_string();
+    let mut status_msg = None;
+    let mut opened: Option<Screen> = None;
+
+    let result = loop {
+        let mut app = match App::new(store.as_mut()) {
+            Ok(app) => app,
+            Err(e) => break Err(e),
+        };
+        app.library = library.clone();
+        app.status_msg = status_msg.take();
+        let screen = match &mut opened {
+            Some(screen) => screen,
+            None => opened.insert(Screen::open(output)?),
+        };
+        match event_loop(screen, &mut app) {
+            Ok(Exit::Done(code)) => break Ok(code),
+            Ok(Exit::Switch(name)) => {
+                drop(app);
+                match store::open_store_in(&name) {
+                    Ok(next) => {
+                        store = next;
+                        status_msg = Some(format!("Switched to library '{}'", name));
+                        library = name;
+                    }
+                    Err(e) => status_msg = Some(format!("Cannot open library: {}", e)),
+                }
+            }
+            Err(e) => break Err(e),
+        }
+    };
 
-    let mut selected_code: Option<&str> = None;
+    if let Some(screen) = opened {
+        screen.close()?;
+    }
+    result
+}
 
+/// Handle key presses until the user picks a snippet, quits or switches library.
+fn event_loop(screen: &mut Screen, app: &mut App) -> io::Result<Exit> {
+    let keys = &config::get().tui.keys;
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        screen.terminal.draw(|f| ui(f, app))?;
 
         if let Event::Key(key) = event::read()? {
-            match app.mode {
+            match &mut app.mode {
+                Mode::Form(form, _) => match form.handle_key(key) {
+                    FormEvent::Submit => {
+                        if let Mode::Form(form, action) =
+                            std::mem::replace(&mut app.mode, Mode::Normal)
+                        {
+                            let mut edit_body = |initial: &str| edit_externally(screen, initial);
+                            app.status_msg =
+                                Some(match app.submit_form(form, action, &mut edit_body) {
+                                    Ok(msg) => msg,
+                                    Err(e) => format!("Save failed: {}", e),
+                                });
+                        }
+                    }
+                    FormEvent::Cancel => {
+                        app.mode = Mode::Normal;
+                        app.status_msg = Some("Canceled.".to_string());
+                    }
+                    FormEvent::Pending => {}
+                },
+                Mode::PickLibrary { names, state } => match key.code {
+                    KeyCode::Down => {
+                        state.select(state.selected().map(|i| (i + 1) % names.len().max(1)));
+                    }
+                    KeyCode::Up => {
+                        let len = names.len().max(1);
+                        state.select(state.selected().map(|i| (i + len - 1) % len));
+                    }
+                    KeyCode::Enter => {
+                        let picked = state.selected().and_then(|i| names.get(i)).cloned();
+                        app.mode = Mode::Normal;
+                        if let Some(name) = picked.filter(|name| *name != app.library) {
+                            app.switch_to = Some(name);
+                        }
+                    }
+                    KeyCode::Esc => app.mode = Mode::Normal,
+                    _ => {}
+                },
+                Mode::ConfirmRun { command } => match key.code {
+                    KeyCode::Char('y') => {
+                        let command = std::mem::take(command);
+                        app.mode = Mode::Normal;
+                        app.status_msg = Some(match run_externally(screen, &command) {
+                            Ok(status) => format!("Command {}.", runner::describe(status)),
+                            Err(e) => format!("Run failed: {}", e),
+                        });
+                    }
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.mode = Mode::Normal;
+                        app.status_msg = Some("Canceled run.".to_string());
+                    }
+                    _ => {}
+                },
                 Mode::ConfirmDelete => match key.code {
                     KeyCode::Char('y') => {
-                        if let Some(sel) = app.list_state.selected() {
-                            if let Some(&idx) = app.visible_snippets.get(sel) {
-                                let id = app.all_snippets[idx].id.clone();
-                                match delete_snippet(&id) {
-                                    Ok(_) => {
-                                        app.all_snippets.retain(|s| s.id != id);
-                                        app.filter_snippets();
-
-                                        if app.visible_snippets.is_empty() {
-                                            app.list_state.select(None);
-                                        } else {
-                                            let new_sel = sel.min(app.visible_snippets.len() - 1);
-                                            app.list_state.select(Some(new_sel));
-                                        }
-                                        app.status_msg = Some("Deleted snippet.".to_string());
-                                    }
-                                    Err(e) => {
-                                        app.status_msg = Some(format!("Delete failed: {}", e));
-                                    }
-                                }
-                            }
-                        }
+                        app.status_msg = Some(match app.delete_selected() {
+                            Ok(()) => format!("Deleted snippet ({} to undo).", chord(keys.undo)),
+                            Err(e) => format!("Delete failed: {}", e),
+                        });
                         app.mode = Mode::Normal;
                     }
                     KeyCode::Char('n') | KeyCode::Esc => {
@@ -195,23 +813,48 @@ pub fn run_tui(all_snippets: Vec<Snippet>) -> io::Result<Option<String>> {
                     _ => {}
                 },
                 Mode::Normal => match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Enter => {
-                        if let Some(selected_index) = app.list_state.selected() {
-                            if let Some(&selected_snippet) =
-                                app.visible_snippets.get(selected_index)
-                            {
-                                selected_code =
-                                    Some(app.all_snippets[selected_snippet].code.as_str());
-                                break;
-                            }
+                    _ if is_chord(&key, keys.run) => app.choose_selected(Pick::Run),
+                    _ if is_chord(&key, keys.copy_raw) => app.copy_selected_raw(),
+                    _ if is_chord(&key, keys.edit) => {
+                        if !app.selected_is_read_only()
+                            && let Some(mut snippet) = app.selected_snippet().cloned()
+                        {
+                            app.status_msg = Some(match edit_externally(screen, &snippet.code) {
+                                Ok(code) if code == snippet.code => "No changes.".to_string(),
+                                Ok(code) => {
+                                    snippet.code = code;
+                                    match app.save(snippet) {
+                                        Ok(()) => "Updated snippet.".to_string(),
+                                        Err(e) => format!("Save failed: {}", e),
+                                    }
+                                }
+                                Err(e) => format!("Editor failed: {}", e),
+                            });
                         }
                     }
+                    _ if is_chord(&key, keys.details) => app.open_details_form(),
+                    _ if is_chord(&key, keys.fork) => {
+                        app.status_msg = Some(match app.fork_selected() {
+                            Ok(msg) => msg,
+                            Err(e) => format!("Fork failed: {}", e),
+                        });
+                    }
+                    _ if is_chord(&key, keys.add) => app.open_new_form(),
+                    _ if is_chord(&key, keys.library) => app.open_library_picker(),
+                    _ if is_chord(&key, keys.undo) => {
+                        app.status_msg = Some(match app.undo_delete() {
+                            Ok(msg) => msg,
+                            Err(e) => format!("Undo failed: {}", e),
+                        });
+                    }
+                    _ if is_chord(&key, keys.quit) => return Ok(Exit::Done(None)),
+                    KeyCode::Esc => return Ok(Exit::Done(None)),
+                    KeyCode::Enter => app.choose_selected(Pick::Copy),
                     KeyCode::Down => app.next(),
                     KeyCode::Up => app.previous(),
                     KeyCode::PageDown => {
                         let max_lines = app
-                            .selected_snippet()
+                            .previewed_snippet()
                             .map(|s| s.code.lines().count())
                             .unwrap_or(0);
                         let max_scroll = max_lines.saturating_sub(1) as u16;
@@ -220,19 +863,23 @@ pub fn run_tui(all_snippets: Vec<Snippet>) -> io::Result<Option<String>> {
                     KeyCode::PageUp => {
                         app.preview_scroll = app.preview_scroll.saturating_sub(5);
                     }
-                    KeyCode::Char('p') => {
+                    KeyCode::Left => app.page_history(true),
+                    KeyCode::Right => app.page_history(false),
+                    _ if is_chord(&key, keys.preview) => {
                         app.preview_full = !app.preview_full;
                         app.preview_scroll = 0;
                     }
-                    KeyCode::Char('d') => {
-                        app.mode = Mode::ConfirmDelete;
-                        app.status_msg = Some("Confirm delete? press 'y' or 'n'".to_string());
+                    KeyCode::Tab => {
+                        app.sort = app.sort.next();
+                        app.filter_snippets();
+                        app.status_msg = Some(format!("Sorted by {}", app.sort.as_str()));
                     }
+                    _ if is_chord(&key, keys.delete) => app.confirm_delete(),
                     KeyCode::Backspace => {
                         app.search_query.pop();
                         app.filter_snippets();
                     }
-                    KeyCode::Char(c) => {
+                    KeyCode::Char(c) if !is_chord(&key, c) => {
                         app.search_query.push(c);
                         app.filter_snippets();
                     }
@@ -240,28 +887,39 @@ pub fn run_tui(all_snippets: Vec<Snippet>) -> io::Result<Option<String>> {
                 },
             }
         }
+
+        if let Some(code) = app.chosen.take() {
+            return Ok(Exit::Done(Some(code)));
+        }
+        if let Some(name) = app.switch_to.take() {
+            return Ok(Exit::Switch(name));
+        }
     }
+}
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+/// Whether `key` is Ctrl plus the letter `action`. Every action is bound to
+/// such a chord so every letter can be typed into the search box. Ctrl+Alt
+/// is AltGr on some keyboards, so it types.
+fn is_chord(key: &KeyEvent, action: char) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL)
+        && !key.modifiers.contains(KeyModifiers::ALT)
+        && matches!(key.code, KeyCode::Char(c) if c.eq_ignore_ascii_case(&action))
+}
 
-    if let Some(code_to_copy) = selected_code {
-        let mut clipboard = Clipboard::new().expect("Failed to initialize clipboard");
-        clipboard
-            .set_text(code_to_copy)
-            .expect("Failed to copy text to clipboard");
-        return Ok(Some(code_to_copy.to_string()));
-    }
+/// How the chord for `action` is shown, e.g. `^X`.
+fn chord(action: char) -> String {
+    format!("^{}", action.to_ascii_uppercase())
+}
 
-    Ok(None)
+/// Parse a configured color, falling back to `default` if it is invalid.
+fn color(spec: &str, default: Color) -> Color {
+    spec.parse().unwrap_or(default)
 }
 
 fn ui(f: &mut Frame, app: &mut App) {
+    let config = &config::get().tui;
+    let (keys, theme) = (&config.keys, &config.theme);
+    let border = Style::default().fg(color(&theme.border, Color::Reset));
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
@@ -271,14 +929,19 @@ fn ui(f: &mut Frame, app: &mut App) {
     let mut title = "Search".to_string();
     match app.mode {
         Mode::ConfirmDelete => title.push_str(" [confirm delete: y/n]"),
-        Mode::Normal => {}
+        Mode::ConfirmRun { .. } => title.push_str(" [confirm run: y/n]"),
+        Mode::Normal | Mode::Form(..) | Mode::PickLibrary { .. } => {}
     }
     if let Some(msg) = &app.status_msg {
         title.push_str(" • ");
         title.push_str(msg);
     }
-    let search_bar = Paragraph::new(app.search_query.as_str())
-        .block(Block::default().borders(Borders::ALL).title(title));
+    let search_bar = Paragraph::new(app.search_query.as_str()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(border),
+    );
     f.render_widget(search_bar, chunks[0]);
 
     let main_chunks = Layout::default()
@@ -289,55 +952,315 @@ fn ui(f: &mut Frame, app: &mut App) {
     let items: Vec<ListItem> = app
         .visible_snippets
         .iter()
-        .map(|&i| ListItem::new(app.all_snippets[i].description.as_str()))
+        .map(|&i| {
+            let snippet = &app.all_snippets[i];
+            ListItem::new(format!(
+                "{}{}",
+                snippet.origin.marker(),
+                snippet.description
+            ))
+        })
         .collect();
 
+    let highlight = Style::default()
+        .fg(color(&theme.highlight_fg, Color::Reset))
+        .bg(color(&theme.highlight_bg, Color::Rgb(0, 150, 150)))
+        .add_modifier(Modifier::BOLD);
     let snippets_list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Snippets (Enter copy, d delete, p preview, PgUp/PgDn scroll, q quit)"),
-        )
-        .highlight_style(
-            Style::default()
-                .bg(Color::Rgb(0, 150, 150))
-                .add_modifier(Modifier::BOLD),
+                .title(format!(
+                    "Snippets in {} by {} (Enter copy, {} copy raw, {} run, {} add, {} edit, {} details, {} delete, {} undo, {} fork, {} preview, {} library, Tab sort, PgUp/PgDn scroll, Left/Right history, Esc or {} quit)",
+                    app.library,
+                    app.sort.as_str(),
+                    chord(keys.copy_raw),
+                    chord(keys.run),
+                    chord(keys.add),
+                    chord(keys.edit),
+                    chord(keys.details),
+                    chord(keys.delete),
+                    chord(keys.undo),
+                    chord(keys.fork),
+                    chord(keys.preview),
+                    chord(keys.library),
+                    chord(keys.quit)
+                ))
+                .border_style(border),
         )
+        .highlight_style(highlight)
         .highlight_symbol(">> ");
 
     f.render_stateful_widget(snippets_list, main_chunks[0], &mut app.list_state);
 
-    let preview_text = if let Some(s) = app.selected_snippet() {
-        if app.preview_full {
+    let preview_text = if let Mode::ConfirmRun { command } = &app.mode {
+        format!("Run via {}? (y/n)\n\n{}", runner::shell_name(), command)
+    } else if let Some(s) = app.previewed_snippet() {
+        let body = if app.preview_full {
             s.code.clone()
         } else {
             let mut lines: Vec<&str> = s.code.lines().collect();
-            let truncated = if lines.len() > 10 {
-                lines.truncate(10);
+            if lines.len() > config.preview_lines {
+                lines.truncate(config.preview_lines);
                 let mut t = lines.join("\n");
                 t.push_str("\n…");
                 t
             } else {
                 lines.join("\n")
-            };
-            truncated
+            }
+        };
+        let metadata = s.metadata_lines();
+        if metadata.is_empty() {
+            body
+        } else {
+            format!("{}\n\n{}", metadata.join("\n"), body)
         }
     } else {
         String::from("No snippet selected.")
     };
 
+    let size = if app.preview_full { "full" } else { "compact" };
+    let preview_title = match &app.history {
+        Some(view) => {
+            let revision = &view.revisions[view.shown];
+            let when = revision
+                .snippet
+                .updated_at
+                .or(revision.snippet.created_at)
+                .map_or_else(|| "unknown time".to_string(), snippets::format_timestamp);
+            format!(
+                "Preview ({}, revision {} of {}, {}; Right for newer)",
+                size,
+                revision.number,
+                view.revisions.len(),
+                when
+            )
+        }
+        None => format!("Preview ({})", size),
+    };
     let preview = Paragraph::new(preview_text)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(if app.preview_full {
-                    "Preview (full)"
-                } else {
-                    "Preview (compact)"
-                }),
+                .border_style(border)
+                .title(preview_title),
         )
         .wrap(Wrap { trim: false })
         .scroll((app.preview_scroll, 0));
 
     f.render_widget(preview, main_chunks[1]);
+
+    if let Mode::Form(form, _) = &app.mode {
+        form.render(f, f.area());
+    }
+    if let Mode::PickLibrary { names, state } = &mut app.mode {
+        let popup = form::centered(f.area(), 40, (names.len() as u16 + 2).min(f.area().height));
+        let items: Vec<ListItem> = names.iter().map(|n| ListItem::new(n.as_str())).collect();
+        f.render_widget(Clear, popup);
+        f.render_stateful_widget(
+            List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Library (Enter switch, Esc cancel)")
+                        .border_style(border),
+                )
+                .highlight_style(highlight)
+                .highlight_symbol(">> "),
+            popup,
+            state,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsutil::TempDir;
+    use crate::store::MemoryStore;
+
+    fn snippet(description: &str, code: &str) -> Snippet {
+        Snippet::new(description.to_string(), vec![], code.to_string())
+    }
+
+    #[test]
+    fn delete_selected_removes_from_store_and_list() {
+        let mut store = MemoryStore::with(vec![
+            snippet("list files", "ls -la"),
+            snippet("disk usage", "du -sh"),
+        ]);
+        let mut app = App::new(&mut store).unwrap();
+        app.search_query = "disk".to_string();
+        app.filter_snippets();
+        assert_eq!(app.selected_snippet().unwrap().code, "du -sh");
+
+        app.delete_selected().unwrap();
+        assert!(app.selected_snippet().is_none());
+        assert_eq!(app.all_snippets.len(), 1);
+        drop(app);
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn details_form_updates_description_and_tags() {
+        let mut store = MemoryStore::with(vec![snippet("list files", "ls -la")]);
+        let mut app = App::new(&mut store).unwrap();
+        app.open_details_form();
+        let Mode::Form(mut form, action) = std::mem::replace(&mut app.mode, Mode::Normal) else {
+            panic!("form not opened");
+        };
+        form.fields[0].value = "List all files".to_string();
+        form.fields[1].value = "fs, ,shell".to_string();
+
+        app.submit_form(form, action, &mut |_| unreachable!())
+            .unwrap();
+        let selected = app.selected_snippet().unwrap();
+        assert_eq!(selected.description, "List all files");
+        assert_eq!(selected.tags, ["fs", "shell"]);
+        drop(app);
+        assert_eq!(store.list().unwrap()[0].description, "List all files");
+    }
+
+    #[test]
+    fn new_snippet_form_saves_and_is_searchable() {
+        let mut store = MemoryStore::default();
+        let mut app = App::new(&mut store).unwrap();
+        app.open_new_form();
+        let Mode::Form(mut form, action) = std::mem::replace(&mut app.mode, Mode::Normal) else {
+            panic!("form not opened");
+        };
+        form.fields[0].value = "Show git log".to_string();
+        form.fields[2].value = "bash".to_string();
+
+        let msg = app
+            .submit_form(form, action, &mut |_| Ok("git log --oneline".to_string()))
+            .unwrap();
+        assert_eq!(msg, "Added snippet.");
+        app.search_query = "oneline".to_string();
+        app.filter_snippets();
+        assert_eq!(
+            app.selected_snippet().unwrap().language.as_deref(),
+            Some("bash")
+        );
+        drop(app);
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn enter_asks_for_placeholders_before_choosing() {
+        let mut store = MemoryStore::with(vec![snippet("serve", "serve -p {{port:8080}} {{dir}}")]);
+        let mut app = App::new(&mut store).unwrap();
+        app.choose_selected(Pick::Copy);
+        assert!(app.chosen.is_none());
+        let Mode::Form(form, action) = std::mem::replace(&mut app.mode, Mode::Normal) else {
+            panic!("placeholder form not opened");
+        };
+        let msg = app
+            .submit_form(form, action, &mut |_| unreachable!())
+            .unwrap();
+        assert_eq!(msg, "Cannot fill: unfilled placeholders: dir");
+        assert!(app.chosen.is_none());
+        let Mode::Form(mut form, action) = std::mem::replace(&mut app.mode, Mode::Normal) else {
+            panic!("placeholder form closed");
+        };
+        form.fields[0].value.clear();
+        form.fields[1].value = "/srv".to_string();
+
+        app.submit_form(form, action, &mut |_| unreachable!())
+            .unwrap();
+        assert_eq!(app.chosen.as_deref(), Some("serve -p 8080 /srv"));
+
+        app.chosen = None;
+        app.copy_selected_raw();
+        assert_eq!(
+            app.chosen.as_deref(),
+            Some("serve -p {{port:8080}} {{dir}}")
+        );
+    }
+
+    #[test]
+    fn shared_snippets_must_be_forked_before_changing() {
+        let mut shared = snippet("team deploy", "make deploy");
+        shared.origin = Origin::Shared;
+        let mut store = MemoryStore::with(vec![shared]);
+        let mut app = App::new(&mut store).unwrap();
+        app.confirm_delete();
+        assert!(matches!(app.mode, Mode::Normal));
+        app.open_details_form();
+        assert!(matches!(app.mode, Mode::Normal));
+
+        assert_eq!(app.fork_selected().unwrap(), "Forked into your library.");
+        assert_eq!(app.selected_snippet().unwrap().origin, Origin::User);
+        app.confirm_delete();
+        assert!(matches!(app.mode, Mode::ConfirmDelete));
+    }
+
+    #[test]
+    fn only_ctrl_chords_trigger_letter_actions() {
+        let key = |c, modifiers| KeyEvent::new(KeyCode::Char(c), modifiers);
+        assert!(is_chord(&key('x', KeyModifiers::CONTROL), 'x'));
+        assert!(is_chord(&key('l', KeyModifiers::CONTROL), 'L'));
+        assert!(!is_chord(&key('x', KeyModifiers::NONE), 'x'));
+        assert!(!is_chord(
+            &key('x', KeyModifiers::CONTROL | KeyModifiers::ALT),
+            'x'
+        ));
+        assert_eq!(chord('u'), "^U");
+    }
+
+    #[test]
+    fn preview_pages_through_earlier_revisions() {
+        let dir = TempDir::new();
+        let mut store = store::JsonDirStore::new(dir.join("snippets"));
+        store.put(&snippet("greet", "echo hi")).unwrap();
+        let mut app = App::new(&mut store).unwrap();
+        app.page_history(true);
+        assert!(app.history.is_none());
+
+        let mut edited = app.selected_snippet().unwrap().clone();
+        edited.code = "echo hello".to_string();
+        app.save(edited).unwrap();
+        app.page_history(true);
+        assert_eq!(app.previewed_snippet().unwrap().code, "echo hi");
+        app.page_history(true);
+        assert_eq!(app.status_msg.as_deref(), Some("Oldest revision."));
+        app.page_history(false);
+        assert!(app.history.is_none());
+        assert_eq!(app.previewed_snippet().unwrap().code, "echo hello");
+    }
+
+    #[test]
+    fn undo_restores_the_last_deleted_snippet() {
+        let dir = TempDir::new();
+        let mut store = store::JsonDirStore::new(dir.join("snippets"));
+        store.put(&snippet("list files", "ls -la")).unwrap();
+        store.put(&snippet("disk usage", "du -sh")).unwrap();
+        let mut app = App::new(&mut store).unwrap();
+        assert_eq!(app.undo_delete().unwrap(), "Nothing to undo.");
+
+        app.delete_selected().unwrap();
+        app.delete_selected().unwrap();
+        assert!(app.all_snippets.is_empty());
+        app.undo_delete().unwrap();
+        app.undo_delete().unwrap();
+        assert_eq!(app.all_snippets.len(), 2);
+        assert!(app.selected_snippet().is_some());
+        drop(app);
+        assert_eq!(store.list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn run_asks_for_confirmation_with_the_rendered_command() {
+        let mut store = MemoryStore::with(vec![snippet("greet", "echo {{who:world}}")]);
+        let mut app = App::new(&mut store).unwrap();
+        app.choose_selected(Pick::Run);
+        let Mode::Form(form, action) = std::mem::replace(&mut app.mode, Mode::Normal) else {
+            panic!("placeholder form not opened");
+        };
+
+        app.submit_form(form, action, &mut |_| unreachable!())
+            .unwrap();
+        assert!(app.chosen.is_none());
+        assert!(matches!(&app.mode, Mode::ConfirmRun { command } if command == "echo world"));
+    }
 }
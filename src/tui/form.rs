@@ -0,0 +1,154 @@
+//! Small multi-field text form drawn as a popup over the main TUI.
+//!
+//! Key bindings while a form is open:
+//! - Type / Backspace: edit the focused field
+//! - Tab/Down, Shift-Tab/Up: move between fields
+//! - Enter: submit
+//! - Esc: cancel
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// A labelled single-line text field.
+pub struct Field {
+    pub label: String,
+    pub value: String,
+}
+
+/// What the user did with the form after a key press.
+pub enum FormEvent {
+    /// Still editing.
+    Pending,
+    /// Enter was pressed.
+    Submit,
+    /// Esc was pressed.
+    Cancel,
+}
+
+pub struct Form {
+    pub title: String,
+    pub fields: Vec<Field>,
+    focus: usize,
+}
+
+impl Form {
+    /// Build a form from `(label, initial value)` pairs; the first field has focus.
+    pub fn new<L: Into<String>>(title: impl Into<String>, fields: Vec<(L, String)>) -> Self {
+        Self {
+            title: title.into(),
+            fields: fields
+                .into_iter()
+                .map(|(label, value)| Field {
+                    label: label.into(),
+                    value,
+                })
+                .collect(),
+            focus: 0,
+        }
+    }
+
+    /// Current value of the field at `index`.
+    pub fn value(&self, index: usize) -> &str {
+        self.fields.get(index).map_or("", |f| f.value.as_str())
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> FormEvent {
+        match key.code {
+            KeyCode::Enter => return FormEvent::Submit,
+            KeyCode::Esc => return FormEvent::Cancel,
+            KeyCode::Tab | KeyCode::Down => {
+                self.focus = (self.focus + 1) % self.fields.len().max(1);
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                let len = self.fields.len().max(1);
+                self.focus = (self.focus + len - 1) % len;
+            }
+            KeyCode::Backspace => {
+                if let Some(field) = self.fields.get_mut(self.focus) {
+                    field.value.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(field) = self.fields.get_mut(self.focus) {
+                    field.value.push(c);
+                }
+            }
+            _ => {}
+        }
+        FormEvent::Pending
+    }
+
+    /// Draw the form centered over `area`.
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let height = (self.fields.len() as u16 + 3).min(area.height);
+        let popup = centered(area, 70, height);
+        let label_width = self
+            .fields
+            .iter()
+            .map(|field| field.label.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut lines: Vec<Line> = self
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let focused = i == self.focus;
+                let style = if focused {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let cursor = if focused { "▏" } else { "" };
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>width$}: ", field.label, width = label_width),
+                        style,
+                    ),
+                    Span::raw(format!("{}{}", field.value, cursor)),
+                ])
+            })
+            .collect();
+        lines.push(Line::from(Span::styled(
+            "Enter save • Tab next field • Esc cancel",
+            Style::default().add_modifier(Modifier::DIM),
+        )));
+
+        f.render_widget(Clear, popup);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(self.title.as_str()),
+            ),
+            popup,
+        );
+    }
+}
+
+/// A rectangle `percent_x` wide and `height` tall, centered in `area`.
+pub(super) fn centered(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Fill(1),
+            Constraint::Length(height),
+            Constraint::Fill(1),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}